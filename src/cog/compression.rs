@@ -2,13 +2,16 @@
 // https://exiftool.org/TagNames/EXIF.html#Compression
 // https://github.com/image-rs/image-tiff/blob/master/src/decoder/mod.rs
 
-// TODO support jpeg
 // TODO decide on miniz_oxide vs flate2
 
-use std::io::{self, Read, Write};
+use std::io::{self, Cursor, Read, Write};
 // use miniz_oxide::inflate::{self,TINFLStatus};
+use crate::raster::SampleFormat;
+use crate::tiff::Endian;
+use eio::{FromBytes, ToBytes};
 use flate2;
 use num_enum::{FromPrimitive, IntoPrimitive};
+use num_traits::{WrappingAdd, WrappingSub};
 use salzweg::decoder::{DecodingError, TiffStyleDecoder};
 use salzweg::encoder::{EncodingError, TiffStyleEncoder};
 
@@ -16,16 +19,25 @@ use salzweg::encoder::{EncodingError, TiffStyleEncoder};
 pub enum DecompressError {
     LzwDecodeError(DecodingError),
     LzwEncodeError(EncodingError),
+    DeflateDecodeError(io::Error),
+    DeflateEncodeError(io::Error),
+    ZstdDecodeError(io::Error),
+    ZstdEncodeError(io::Error),
+    PackBitsDecodeError(String),
+    LzmaDecodeError(lzma_rs::error::Error),
+    JpegDecodeError(jpeg_decoder::Error),
+    WebPDecodeError(image_webp::DecodingError),
+    LercDecodeError(String),
+    LercModeNotSupported(u8),         // Lerc2 image encode mode
+    LercChecksumMismatch((u32, u32)), // (expected, computed)
+    #[cfg(feature = "webp")]
+    WebPEncodeError(webp::WebPEncodingError),
     // InflateError(TINFLStatus),
     CompressionNotSupported(Compression),
     PredictorNotSupported(Predictor),
-    IoError(io::Error),
-}
-
-impl From<io::Error> for DecompressError {
-    fn from(e: io::Error) -> Self {
-        DecompressError::IoError(e)
-    }
+    PredictorBitDepthNotSupported((Predictor, usize)),
+    PredictorSampleFormatNotSupported((Predictor, SampleFormat)),
+    OutputLimit(usize), // decoded more than the limit, see Compression::decode_with_limit
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, IntoPrimitive, FromPrimitive)]
@@ -95,32 +107,162 @@ impl Compression {
             }
             Self::DeflateAdobe => {
                 let mut buf = vec![];
-                flate2::read::ZlibDecoder::new(bytes).read_to_end(&mut buf)?;
+                flate2::read::ZlibDecoder::new(bytes)
+                    .read_to_end(&mut buf)
+                    .map_err(DecompressError::DeflateDecodeError)?;
                 Ok(buf)
                 // inflate::decompress_to_vec_zlib(bytes).map_err(|e| DecompressError::InflateError(e.status))
             }
+            Self::Zstd => zstd::stream::decode_all(bytes).map_err(DecompressError::ZstdDecodeError),
+            Self::PackBits => decode_packbits(bytes, usize::MAX),
+            Self::LZMA2 => {
+                // libtiff writes xz streams
+                let mut buf = vec![];
+                lzma_rs::xz_decompress(&mut Cursor::new(bytes), &mut buf)
+                    .map_err(DecompressError::LzmaDecodeError)?;
+                Ok(buf)
+            }
             other => Err(DecompressError::CompressionNotSupported(*other)),
         }
     }
 
+    /// Decode at most max_bytes, streams that expand further fail with OutputLimit
+    pub fn decode_with_limit(
+        &self,
+        bytes: &[u8],
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, DecompressError> {
+        let mut writer = LimitedWriter {
+            buffer: vec![],
+            limit: max_bytes,
+            exceeded: false,
+        };
+        let result = match self {
+            Self::Lzw => TiffStyleDecoder::decode(bytes, &mut writer)
+                .map_err(DecompressError::LzwDecodeError),
+            Self::DeflateAdobe => io::copy(&mut flate2::read::ZlibDecoder::new(bytes), &mut writer)
+                .map(|_| ())
+                .map_err(DecompressError::DeflateDecodeError),
+            Self::Zstd => zstd::stream::copy_decode(bytes, &mut writer)
+                .map_err(DecompressError::ZstdDecodeError),
+            Self::LZMA2 => lzma_rs::xz_decompress(&mut Cursor::new(bytes), &mut writer)
+                .map_err(DecompressError::LzmaDecodeError),
+            // Runs expand 64 times, the limit is checked as they are decoded
+            Self::PackBits => {
+                decode_packbits(bytes, max_bytes).map(|buffer| writer.buffer = buffer)
+            }
+            // Output is bounded by the input
+            other => other.decode(bytes).and_then(|buffer| {
+                writer
+                    .write_all(&buffer)
+                    .map_err(DecompressError::DeflateDecodeError)
+            }),
+        };
+        match (writer.exceeded, result) {
+            (true, _) => Err(DecompressError::OutputLimit(max_bytes)),
+            (false, Err(e)) => Err(e),
+            (false, Ok(())) => Ok(writer.buffer),
+        }
+    }
+
     pub fn encode(&self, bytes: &[u8]) -> Result<Vec<u8>, DecompressError> {
+        self.encode_with_level(bytes, None)
+    }
+
+    // Level is codec specific, e.g. 0-9 for Deflate and 1-22 for Zstd
+    pub fn encode_with_level(
+        &self,
+        bytes: &[u8],
+        level: Option<i32>,
+    ) -> Result<Vec<u8>, DecompressError> {
         match self {
             Self::Uncompressed => Ok(bytes.to_vec()),
             Self::Lzw => {
                 TiffStyleEncoder::encode_to_vec(bytes).map_err(DecompressError::LzwEncodeError)
             }
             Self::DeflateAdobe => {
-                let mut encoder =
-                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
-                encoder.write_all(bytes)?;
-                Ok(encoder.finish()?)
+                let level = match level {
+                    Some(level) => flate2::Compression::new(level.clamp(0, 9) as u32),
+                    None => flate2::Compression::default(),
+                };
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), level);
+                encoder
+                    .write_all(bytes)
+                    .map_err(DecompressError::DeflateEncodeError)?;
+                encoder
+                    .finish()
+                    .map_err(DecompressError::DeflateEncodeError)
                 // inflate::decompress_to_vec_zlib(bytes).map_err(|e| DecompressError::InflateError(e.status))
             }
+            Self::Zstd => {
+                let level = level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+                zstd::bulk::compress(bytes, level).map_err(DecompressError::ZstdEncodeError)
+            }
             other => Err(DecompressError::CompressionNotSupported(*other)),
         }
     }
 }
 
+// https://www.adobe.io/content/dam/udp/en/open/standards/tiff/TIFF6.pdf (Section 9)
+//   Header byte n: 0..=127 copies the next n + 1 bytes, -127..=-1 repeats the next byte 1 - n times
+fn decode_packbits(bytes: &[u8], max_bytes: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut buf = Vec::with_capacity(bytes.len().saturating_mul(2).min(max_bytes));
+    let mut i = 0;
+    while i < bytes.len() {
+        let n = bytes[i] as i8;
+        i += 1;
+        match n {
+            0..=127 => {
+                let count = n as usize + 1;
+                let literal = bytes.get(i..i + count).ok_or_else(|| {
+                    DecompressError::PackBitsDecodeError(format!(
+                        "Literal run of {count} bytes overruns input at {i}"
+                    ))
+                })?;
+                buf.extend_from_slice(literal);
+                i += count;
+            }
+            -127..=-1 => {
+                let value = *bytes.get(i).ok_or_else(|| {
+                    DecompressError::PackBitsDecodeError(format!("Missing repeated byte at {i}"))
+                })?;
+                buf.extend(std::iter::repeat_n(value, (1 - n as isize) as usize));
+                i += 1;
+            }
+            -128 => {} // No-op
+        }
+        if buf.len() > max_bytes {
+            return Err(DecompressError::OutputLimit(max_bytes));
+        }
+    }
+    Ok(buf)
+}
+
+// Writer that fails once more than its limit is written, against decompression bombs
+struct LimitedWriter {
+    buffer: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len().saturating_add(buf.len()) > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other(format!(
+                "Decoded more than {} bytes",
+                self.limit
+            )));
+        }
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy, IntoPrimitive, FromPrimitive)]
 #[repr(u16)]
 pub enum Predictor {
@@ -133,26 +275,105 @@ pub enum Predictor {
 }
 
 impl Predictor {
+    // Whether the predictor applies to pixels of these samples, checked before any data is touched
+    pub fn check(
+        &self,
+        bits_per_sample: &[u16],
+        sample_format: &[SampleFormat],
+    ) -> Result<(), DecompressError> {
+        if *self == Self::No {
+            return Ok(());
+        }
+        // All samples are differenced as the same type
+        let bit_depth = bits_per_sample.first().copied().unwrap_or(0);
+        if let Some(bits) = bits_per_sample.iter().find(|bits| **bits != bit_depth) {
+            return Err(DecompressError::PredictorBitDepthNotSupported((
+                *self,
+                *bits as usize,
+            )));
+        }
+        match self {
+            Self::Horizontal if matches!(bit_depth, 8 | 16 | 32 | 64) => Ok(()),
+            Self::FloatingPoint if matches!(bit_depth, 16 | 32 | 64) => {
+                match sample_format.iter().find(|f| **f != SampleFormat::Float) {
+                    Some(format) => Err(DecompressError::PredictorSampleFormatNotSupported((
+                        *self, *format,
+                    ))),
+                    None => Ok(()),
+                }
+            }
+            Self::Horizontal | Self::FloatingPoint => Err(
+                DecompressError::PredictorBitDepthNotSupported((*self, bit_depth as usize)),
+            ),
+            other => Err(DecompressError::PredictorNotSupported(*other)),
+        }
+    }
+
+    // Reverse the predictor after decompression
     pub fn predict(
         &self,
         buffer: &mut [u8],
         width: usize,
         bit_depth: usize,
         samples_per_pixel: usize,
+        endian: Endian,
+    ) -> Result<(), DecompressError> {
+        self.apply(buffer, width, bit_depth, samples_per_pixel, endian, false)
+    }
+
+    // Apply the predictor before compression
+    pub fn encode(
+        &self,
+        buffer: &mut [u8],
+        width: usize,
+        bit_depth: usize,
+        samples_per_pixel: usize,
+        endian: Endian,
+    ) -> Result<(), DecompressError> {
+        self.apply(buffer, width, bit_depth, samples_per_pixel, endian, true)
+    }
+
+    fn apply(
+        &self,
+        buffer: &mut [u8],
+        width: usize,
+        bit_depth: usize,
+        samples_per_pixel: usize,
+        endian: Endian,
+        encode: bool,
     ) -> Result<(), DecompressError> {
         match self {
             Self::No => {}
             Self::Horizontal => {
-                assert!(
-                    bit_depth <= 8,
-                    "Bit depth {bit_depth} not supported for Horizontal Predictor"
-                );
                 let row_bytes = width * samples_per_pixel * bit_depth / 8;
-                for i in 0..buffer.len() {
-                    if i % row_bytes < samples_per_pixel {
-                        continue;
+                if row_bytes == 0 {
+                    return Err(DecompressError::PredictorBitDepthNotSupported((
+                        *self, bit_depth,
+                    )));
+                }
+                let rows = buffer.chunks_mut(row_bytes);
+                let samples = samples_per_pixel;
+                match bit_depth {
+                    8 => rows.for_each(|row| horizontal::<1, u8>(row, samples, endian, encode)),
+                    16 => rows.for_each(|row| horizontal::<2, u16>(row, samples, endian, encode)),
+                    32 => rows.for_each(|row| horizontal::<4, u32>(row, samples, endian, encode)),
+                    64 => rows.for_each(|row| horizontal::<8, u64>(row, samples, endian, encode)),
+                    _ => {
+                        return Err(DecompressError::PredictorBitDepthNotSupported((
+                            *self, bit_depth,
+                        )))
                     }
-                    buffer[i] = buffer[i].wrapping_add(buffer[i - samples_per_pixel]);
+                }
+            }
+            Self::FloatingPoint => {
+                let row_bytes = width * samples_per_pixel * bit_depth / 8;
+                if !matches!(bit_depth, 16 | 32 | 64) || row_bytes == 0 {
+                    return Err(DecompressError::PredictorBitDepthNotSupported((
+                        *self, bit_depth,
+                    )));
+                }
+                for row in buffer.chunks_mut(row_bytes) {
+                    floating_point(row, samples_per_pixel, bit_depth / 8, endian, encode);
                 }
             }
             other => return Err(DecompressError::PredictorNotSupported(*other)),
@@ -160,3 +381,64 @@ impl Predictor {
         Ok(())
     }
 }
+
+// https://chriscox.org/TIFFTN3d1.pdf
+//   Sample bytes are shuffled into planes, most significant first, then differenced bytewise.
+fn floating_point(row: &mut [u8], samples: usize, bytes: usize, endian: Endian, encode: bool) {
+    let count = row.len() / bytes;
+    let plane = |byte: usize| match endian {
+        Endian::Big => byte,
+        Endian::Little => bytes - byte - 1,
+    };
+    if encode {
+        let shuffled = row.to_vec();
+        for i in 0..count {
+            for byte in 0..bytes {
+                row[plane(byte) * count + i] = shuffled[i * bytes + byte];
+            }
+        }
+        for i in (samples..row.len()).rev() {
+            row[i] = row[i].wrapping_sub(row[i - samples]);
+        }
+    } else {
+        for i in samples..row.len() {
+            row[i] = row[i].wrapping_add(row[i - samples]);
+        }
+        let planes = row.to_vec();
+        for i in 0..count {
+            for byte in 0..bytes {
+                row[i * bytes + byte] = planes[plane(byte) * count + i];
+            }
+        }
+    }
+}
+
+// Differencing wraps around in the sample's integer type
+fn horizontal<const N: usize, T>(row: &mut [u8], samples: usize, endian: Endian, encode: bool)
+where
+    T: FromBytes<N> + ToBytes<N> + WrappingAdd + WrappingSub + Copy,
+{
+    let mut values: Vec<T> = row
+        .chunks_exact(N)
+        .map(|chunk| {
+            let mut bytes = [0; N];
+            bytes.copy_from_slice(chunk);
+            match endian {
+                Endian::Big => T::from_be_bytes(bytes),
+                Endian::Little => T::from_le_bytes(bytes),
+            }
+        })
+        .collect();
+    if encode {
+        for i in (samples..values.len()).rev() {
+            values[i] = values[i].wrapping_sub(&values[i - samples]);
+        }
+    } else {
+        for i in samples..values.len() {
+            values[i] = values[i].wrapping_add(&values[i - samples]);
+        }
+    }
+    for (chunk, value) in row.chunks_exact_mut(N).zip(values) {
+        chunk.copy_from_slice(&endian.encode(value));
+    }
+}
@@ -0,0 +1,170 @@
+// Deferred TileOffsets and TileByteCounts
+//   Large COGs have megabytes of tile entries, which CloudTiff::open_from_range_reader leaves in the
+//   file. Only the entries of the tiles being read are fetched, see Level::load_tile_entries.
+//   Loaded entries are shared by clones of the level, so every render reuses them.
+
+use crate::tiff::{Tag, TagId, TiffError};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::CloudTiffError;
+
+// Entries closer than this in the file are fetched together
+const MAX_GAP: u64 = 4 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct DeferredTiles {
+    pub offsets: DeferredValues,
+    pub byte_counts: DeferredValues,
+    pub planes: Vec<usize>, // plane of the file for each plane of the level, see Level::select_bands
+}
+
+// Values of a tag array that are known so far, by index
+#[derive(Clone, Debug)]
+pub struct DeferredValues {
+    pub tag: Tag,
+    pub loaded: Arc<Mutex<HashMap<usize, u64>>>,
+}
+
+impl DeferredTiles {
+    /// None unless one of the tags was deferred by the parser
+    pub fn from_tags(offsets: &Tag, byte_counts: &Tag, planes: usize) -> Option<Self> {
+        if offsets.deferred.is_none() && byte_counts.deferred.is_none() {
+            return None;
+        }
+        Some(Self {
+            offsets: DeferredValues::new(offsets),
+            byte_counts: DeferredValues::new(byte_counts),
+            planes: (0..planes).collect(),
+        })
+    }
+
+    /// Byte range of a tile's plane, if its entries are loaded
+    pub fn get(
+        &self,
+        index: usize,
+        plane: usize,
+        tile_count: usize,
+    ) -> Result<(u64, u64), CloudTiffError> {
+        let i = self.entry_index(index, plane, tile_count);
+        if i >= self.offsets.tag.count.min(self.byte_counts.tag.count) {
+            return Err(CloudTiffError::TileIndexOutOfRange((
+                i,
+                self.offsets.tag.count.min(self.byte_counts.tag.count),
+            )));
+        }
+        match (self.offsets.get(i), self.byte_counts.get(i)) {
+            (Some(offset), Some(byte_count)) => offset
+                .checked_add(byte_count)
+                .map(|end| (offset, end))
+                .ok_or_else(|| bad_tag(&self.offsets.tag)),
+            _ => Err(CloudTiffError::TileEntryNotLoaded(i)),
+        }
+    }
+
+    /// File byte ranges holding the missing entries of these tiles
+    pub fn missing_ranges(&self, indices: &[usize], tile_count: usize) -> Vec<Range<u64>> {
+        let entries: Vec<usize> = (0..self.planes.len())
+            .flat_map(|plane| {
+                indices
+                    .iter()
+                    .map(move |index| self.entry_index(*index, plane, tile_count))
+            })
+            .collect();
+        let mut ranges = self.offsets.missing_ranges(&entries);
+        ranges.extend(self.byte_counts.missing_ranges(&entries));
+        ranges
+    }
+
+    /// Load entries from bytes read at the start of a range given by missing_ranges
+    pub fn provide(&self, start: u64, bytes: &[u8]) -> Result<(), CloudTiffError> {
+        self.offsets.provide(start, bytes)?;
+        self.byte_counts.provide(start, bytes)
+    }
+
+    fn entry_index(&self, index: usize, plane: usize, tile_count: usize) -> usize {
+        self.planes.get(plane).copied().unwrap_or(plane) * tile_count + index
+    }
+}
+
+impl DeferredValues {
+    pub fn new(tag: &Tag) -> Self {
+        // Tags that were read are loaded entirely
+        let loaded = match tag.deferred {
+            Some(_) => HashMap::new(),
+            None => tag
+                .values::<u64>()
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+                .collect(),
+        };
+        Self {
+            tag: Tag {
+                data: vec![],
+                ..tag.clone()
+            },
+            loaded: Arc::new(Mutex::new(loaded)),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<u64> {
+        self.loaded().get(&index).copied()
+    }
+
+    // Entries are inserted whole, a poisoned map is still consistent
+    fn loaded(&self) -> MutexGuard<'_, HashMap<usize, u64>> {
+        self.loaded.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn missing_ranges(&self, entries: &[usize]) -> Vec<Range<u64>> {
+        let loaded = self.loaded();
+        let mut missing: Vec<usize> = entries
+            .iter()
+            .copied()
+            .filter(|i| *i < self.tag.count && !loaded.contains_key(i))
+            .collect();
+        drop(loaded);
+        missing.sort_unstable();
+        missing.dedup();
+
+        let mut ranges: Vec<Range<u64>> = vec![];
+        for i in missing {
+            let Some(range) = self.tag.deferred_range(i, i + 1) else {
+                continue;
+            };
+            match ranges.last_mut() {
+                Some(last) if range.start <= last.end + MAX_GAP => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+
+    fn provide(&self, start: u64, bytes: &[u8]) -> Result<(), CloudTiffError> {
+        let Some(range) = self.tag.deferred_range(0, self.tag.count) else {
+            return Ok(());
+        };
+        let end = start + bytes.len() as u64;
+        if start < range.start || end > range.end {
+            return Ok(()); // Bytes of the other tag
+        }
+        let size = self.tag.datatype.size_in_bytes() as u64;
+        if !(start - range.start).is_multiple_of(size) {
+            return Err(bad_tag(&self.tag));
+        }
+        let first = ((start - range.start) / size) as usize;
+        let values = self
+            .tag
+            .deferred_values::<u64>(bytes)
+            .ok_or_else(|| bad_tag(&self.tag))?;
+        self.loaded()
+            .extend(values.into_iter().enumerate().map(|(i, v)| (first + i, v)));
+        Ok(())
+    }
+}
+
+fn bad_tag(tag: &Tag) -> CloudTiffError {
+    CloudTiffError::BadTiff(TiffError::BadTag(tag.id().unwrap_or(TagId::TileOffsets)))
+}
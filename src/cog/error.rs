@@ -14,8 +14,10 @@ pub type Region = (f64, f64, f64, f64);
 pub enum CloudTiffError {
     BadTiff(TiffError),
     BadGeoTiff(GeoTiffError),
+    BadMetadata(String),
     TileLevelOutOfRange((usize, usize)),
     TileIndexOutOfRange((usize, usize)),
+    TileEntryNotLoaded(usize), // deferred entry, see Level::load_tile_entries
     ImageCoordOutOfRange((f64, f64)),
     ReadError(io::Error),
     DecompresionError(DecompressError),
@@ -63,7 +65,12 @@ impl From<io::Error> for CloudTiffError {
 
 impl From<DecompressError> for CloudTiffError {
     fn from(e: DecompressError) -> Self {
-        CloudTiffError::DecompresionError(e)
+        match e {
+            DecompressError::OutputLimit(limit) => {
+                CloudTiffError::BadTiff(TiffError::TileTooLarge(limit))
+            }
+            e => CloudTiffError::DecompresionError(e),
+        }
     }
 }
 
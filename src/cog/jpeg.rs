@@ -0,0 +1,72 @@
+// JPEG-in-TIFF (Compression 7)
+// https://www.awaresystems.be/imaging/tiff/specification/TIFFTechNote2.txt
+//   Tiles are usually abbreviated JPEG streams that share the quantization and huffman tables
+//   stored once in the JPEGTables tag. Both are complete SOI..EOI streams, so they are merged by
+//   dropping the EOI of the tables and the SOI of the tile.
+
+use super::compression::DecompressError;
+use crate::raster::PhotometricInterpretation;
+use crate::tiff::Endian;
+use jpeg_decoder::{ColorTransform, Decoder, PixelFormat};
+
+const SOI: [u8; 2] = [0xFF, 0xD8];
+const EOI: [u8; 2] = [0xFF, 0xD9];
+
+pub fn decode(
+    bytes: &[u8],
+    tables: Option<&[u8]>,
+    interpretation: PhotometricInterpretation,
+    endian: Endian,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, (u32, u32)), DecompressError> {
+    let stream = match tables {
+        Some(tables) if tables.len() > 4 => merge_tables(tables, bytes),
+        _ => bytes.to_vec(),
+    };
+
+    let mut decoder = Decoder::new(stream.as_slice());
+    match interpretation {
+        // YCbCr is converted to RGB by the decoder
+        PhotometricInterpretation::YCbCr => decoder.set_color_transform(ColorTransform::YCbCr),
+        PhotometricInterpretation::RGB => decoder.set_color_transform(ColorTransform::RGB),
+        PhotometricInterpretation::CMYK => decoder.set_color_transform(ColorTransform::CMYK),
+        PhotometricInterpretation::BlackIsZero | PhotometricInterpretation::WhiteIsZero => {
+            decoder.set_color_transform(ColorTransform::Grayscale)
+        }
+        _ => {}
+    }
+    decoder
+        .read_info()
+        .map_err(DecompressError::JpegDecodeError)?;
+    if let Some(info) = decoder.info() {
+        let size = info.width as usize * info.height as usize * info.pixel_format.pixel_bytes();
+        if size > max_bytes {
+            return Err(DecompressError::OutputLimit(max_bytes));
+        }
+    }
+    let buffer = decoder.decode().map_err(DecompressError::JpegDecodeError)?;
+    let info = decoder.info().ok_or(DecompressError::JpegDecodeError(
+        jpeg_decoder::Error::Format("Missing frame info".into()),
+    ))?;
+    let dimensions = (info.width as u32, info.height as u32);
+
+    let buffer = match info.pixel_format {
+        // Lossless 16 bit output is native endian
+        PixelFormat::L16 => buffer
+            .chunks_exact(2)
+            .flat_map(|c| endian.encode(u16::from_ne_bytes([c[0], c[1]])))
+            .collect(),
+        _ => buffer,
+    };
+
+    Ok((buffer, dimensions))
+}
+
+fn merge_tables(tables: &[u8], bytes: &[u8]) -> Vec<u8> {
+    let tables = tables.strip_suffix(&EOI).unwrap_or(tables);
+    let bytes = bytes.strip_prefix(&SOI).unwrap_or(bytes);
+    let mut stream = Vec::with_capacity(tables.len() + bytes.len());
+    stream.extend_from_slice(tables);
+    stream.extend_from_slice(bytes);
+    stream
+}
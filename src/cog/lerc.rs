@@ -0,0 +1,984 @@
+// LERC (Compression 34887)
+// https://github.com/Esri/lerc
+// https://gdal.org/en/latest/drivers/raster/gtiff.html#creation-options
+//   Limited Error Raster Compression, decodes Lerc2 blobs (versions 1 to 6) as written by GDAL.
+//   GDAL can wrap the blob in Deflate or Zstd, which is signalled by the LercParameters tag.
+//   Decoded samples are written in the file endianness, invalid pixels are masked out.
+//   Blobs from version 3 carry a Fletcher32 checksum, which is verified before decoding.
+//   Version 6 adds a lossless mode for floats (MAX_Z_ERROR=0), see decode_float_lossless.
+
+use super::compression::{Compression, DecompressError};
+use crate::tiff::Endian;
+use std::collections::HashMap;
+
+const FILE_KEY: &[u8] = b"Lerc2 ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LercParameters {
+    pub version: u32,
+    pub additional_compression: Compression,
+}
+
+impl Default for LercParameters {
+    fn default() -> Self {
+        Self {
+            version: 4,
+            additional_compression: Compression::Uncompressed,
+        }
+    }
+}
+
+impl LercParameters {
+    pub fn from_values(values: &[u32]) -> Self {
+        let additional_compression = match values.get(1) {
+            Some(1) => Compression::DeflateAdobe,
+            Some(2) => Compression::Zstd,
+            _ => Compression::Uncompressed,
+        };
+        Self {
+            version: values.first().copied().unwrap_or(4),
+            additional_compression,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LercBlob {
+    pub buffer: Vec<u8>,
+    pub dimensions: (u32, u32),
+    pub depth: usize,
+    pub mask: Option<Vec<u8>>,
+}
+
+pub fn decode(
+    bytes: &[u8],
+    parameters: &LercParameters,
+    endian: Endian,
+    max_bytes: usize,
+) -> Result<LercBlob, DecompressError> {
+    let bytes = parameters
+        .additional_compression
+        .decode_with_limit(bytes, max_bytes)?;
+    let mut reader = Reader::new(&bytes);
+    let header = Header::read(&mut reader)?;
+    header.verify_checksum(&bytes)?;
+    // Values are held as f64 until they are written in the data type
+    let size = header
+        .pixel_count()
+        .checked_mul(header.depth)
+        .and_then(|n| n.checked_mul(size_of::<f64>()));
+    if size.is_none_or(|size| size > max_bytes) {
+        return Err(DecompressError::OutputLimit(max_bytes));
+    }
+    let mask = read_mask(&mut reader, &header)?;
+    let values = read_values(&mut reader, &header, &mask)?;
+
+    // Invalid pixels are zero, or NaN for floats (as in libtiff)
+    let fill = match header.data_type {
+        DataType::Float | DataType::Double => f64::NAN,
+        _ => 0.0,
+    };
+    // Invalid values of valid pixels (with depth) are coded as noData, restore the original value
+    let no_data = header.pass_no_data.then(|| {
+        (
+            header.data_type.cast(header.no_data),
+            header.no_data_original,
+        )
+    });
+    let depth = header.depth;
+    let mut buffer = Vec::with_capacity(values.len() * header.data_type.size());
+    for (i, v) in values.into_iter().enumerate() {
+        let v = match (mask.is_valid(i / depth), no_data) {
+            (false, _) => fill,
+            (true, Some((no_data, original))) if header.data_type.cast(v) == no_data => original,
+            (true, _) => v,
+        };
+        match header.data_type {
+            DataType::Char => buffer.extend(endian.encode(v as i8)),
+            DataType::Byte => buffer.extend(endian.encode(v as u8)),
+            DataType::Short => buffer.extend(endian.encode(v as i16)),
+            DataType::UShort => buffer.extend(endian.encode(v as u16)),
+            DataType::Int => buffer.extend(endian.encode(v as i32)),
+            DataType::UInt => buffer.extend(endian.encode(v as u32)),
+            DataType::Float => buffer.extend(endian.encode(v as f32)),
+            DataType::Double => buffer.extend(endian.encode(v)),
+        }
+    }
+
+    Ok(LercBlob {
+        buffer,
+        dimensions: (header.width as u32, header.height as u32),
+        depth,
+        mask: mask.into_bytes(header.pixel_count()),
+    })
+}
+
+fn error<T>(message: &str) -> Result<T, DecompressError> {
+    Err(DecompressError::LercDecodeError(message.to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum DataType {
+    Char = 0,
+    Byte = 1,
+    Short = 2,
+    UShort = 3,
+    Int = 4,
+    UInt = 5,
+    Float = 6,
+    Double = 7,
+}
+
+impl DataType {
+    fn from_code(code: i32) -> Result<Self, DecompressError> {
+        Ok(match code {
+            0 => Self::Char,
+            1 => Self::Byte,
+            2 => Self::Short,
+            3 => Self::UShort,
+            4 => Self::Int,
+            5 => Self::UInt,
+            6 => Self::Float,
+            7 => Self::Double,
+            _ => return error("Bad data type"),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Char | Self::Byte => 1,
+            Self::Short | Self::UShort => 2,
+            Self::Int | Self::UInt | Self::Float => 4,
+            Self::Double => 8,
+        }
+    }
+
+    // Value as stored in this type
+    fn cast(&self, v: f64) -> f64 {
+        match self {
+            Self::Char => v as i8 as f64,
+            Self::Byte => v as u8 as f64,
+            Self::Short => v as i16 as f64,
+            Self::UShort => v as u16 as f64,
+            Self::Int => v as i32 as f64,
+            Self::UInt => v as u32 as f64,
+            Self::Float => v as f32 as f64,
+            Self::Double => v,
+        }
+    }
+
+    // Tile offsets are stored in the smallest type that fits
+    fn reduced(&self, code: u8) -> Result<Self, DecompressError> {
+        let code = code as i32;
+        let reduced = match self {
+            Self::Short | Self::Int => *self as i32 - code,
+            Self::UShort | Self::UInt => *self as i32 - 2 * code,
+            Self::Float => match code {
+                0 => Self::Float as i32,
+                1 => Self::Short as i32,
+                _ => Self::Byte as i32,
+            },
+            Self::Double => match code {
+                0 => Self::Double as i32,
+                _ => Self::Double as i32 - 2 * code + 1,
+            },
+            _ => *self as i32,
+        };
+        Self::from_code(reduced)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecompressError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len());
+        let Some(end) = end else {
+            return error("Unexpected end of blob");
+        };
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DecompressError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecompressError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, DecompressError> {
+        Ok(i16::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, DecompressError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, DecompressError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u32, DecompressError> {
+        Ok(match size {
+            1 => self.u8()? as u32,
+            2 => u16::from_le_bytes(self.array()?) as u32,
+            4 => u32::from_le_bytes(self.array()?),
+            _ => return error("Bad integer size"),
+        })
+    }
+
+    fn value(&mut self, data_type: DataType) -> Result<f64, DecompressError> {
+        Ok(match data_type {
+            DataType::Char => self.u8()? as i8 as f64,
+            DataType::Byte => self.u8()? as f64,
+            DataType::Short => self.i16()? as f64,
+            DataType::UShort => u16::from_le_bytes(self.array()?) as f64,
+            DataType::Int => self.i32()? as f64,
+            DataType::UInt => u32::from_le_bytes(self.array()?) as f64,
+            DataType::Float => f32::from_le_bytes(self.array()?) as f64,
+            DataType::Double => self.f64()?,
+        })
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+}
+
+struct Header {
+    version: i32,
+    checksum: u32,
+    blob_size: usize,
+    width: usize,
+    height: usize,
+    depth: usize,
+    num_valid: usize,
+    micro_block_size: usize,
+    data_type: DataType,
+    max_z_error: f64,
+    z_min: f64,
+    z_max: f64,
+    pass_no_data: bool,
+    no_data: f64,
+    no_data_original: f64,
+}
+
+impl Header {
+    fn read(reader: &mut Reader) -> Result<Self, DecompressError> {
+        if reader.take(FILE_KEY.len())? != FILE_KEY {
+            return error("Not a Lerc2 blob");
+        }
+        let version = reader.i32()?;
+        if !(1..=6).contains(&version) {
+            return error(&format!("Lerc2 version {version} not supported"));
+        }
+        let checksum = match version {
+            3.. => reader.uint(4)?,
+            _ => 0,
+        };
+
+        let height = reader.i32()?;
+        let width = reader.i32()?;
+        let depth = if version >= 4 { reader.i32()? } else { 1 };
+        let num_valid = reader.i32()?;
+        let micro_block_size = reader.i32()?;
+        let blob_size = reader.i32()?;
+        let data_type = DataType::from_code(reader.i32()?)?;
+        let mut pass_no_data = false;
+        if version >= 6 {
+            let blobs_more = reader.i32()?;
+            if blobs_more > 0 {
+                return error("Multiple Lerc2 blobs not supported");
+            }
+            let flags = reader.take(4)?;
+            pass_no_data = flags[0] != 0;
+        }
+        let max_z_error = reader.f64()?;
+        let z_min = reader.f64()?;
+        let z_max = reader.f64()?;
+        let (no_data, no_data_original) = match version {
+            6.. => (reader.f64()?, reader.f64()?),
+            _ => (0.0, 0.0),
+        };
+
+        if height <= 0
+            || width <= 0
+            || depth <= 0
+            || micro_block_size <= 0
+            || num_valid < 0
+            || blob_size < 0
+        {
+            return error("Bad header");
+        }
+        let (width, height) = (width as usize, height as usize);
+        let num_valid = num_valid as usize;
+        if num_valid > width * height {
+            return error("Bad valid pixel count");
+        }
+
+        Ok(Self {
+            version,
+            checksum,
+            blob_size: blob_size as usize,
+            width,
+            height,
+            depth: depth as usize,
+            num_valid,
+            micro_block_size: micro_block_size as usize,
+            data_type,
+            max_z_error,
+            z_min,
+            z_max,
+            pass_no_data,
+            no_data,
+            no_data_original,
+        })
+    }
+
+    fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    // The checksum covers the blob after its own field
+    fn verify_checksum(&self, bytes: &[u8]) -> Result<(), DecompressError> {
+        if self.version < 3 {
+            return Ok(());
+        }
+        let start = FILE_KEY.len() + 8;
+        let Some(blob) = bytes.get(start..self.blob_size) else {
+            return error("Bad blob size");
+        };
+        let checksum = fletcher32(blob);
+        if checksum != self.checksum {
+            return Err(DecompressError::LercChecksumMismatch((
+                self.checksum,
+                checksum,
+            )));
+        }
+        Ok(())
+    }
+}
+
+// As computed by Lerc2::ComputeChecksumFletcher32
+fn fletcher32(bytes: &[u8]) -> u32 {
+    let (mut sum1, mut sum2) = (0xffff_u32, 0xffff_u32);
+    let words = bytes.chunks_exact(2);
+    let odd = words.remainder();
+    // Blocks of 359 words keep the sums from overflowing
+    for block in bytes[..bytes.len() - odd.len()].chunks(2 * 359) {
+        for word in block.chunks_exact(2) {
+            sum1 += ((word[0] as u32) << 8) + word[1] as u32;
+            sum2 += sum1;
+        }
+        sum1 = (sum1 & 0xffff) + (sum1 >> 16);
+        sum2 = (sum2 & 0xffff) + (sum2 >> 16);
+    }
+    if let Some(byte) = odd.first() {
+        sum1 += (*byte as u32) << 8;
+        sum2 += sum1;
+    }
+    sum1 = (sum1 & 0xffff) + (sum1 >> 16);
+    sum2 = (sum2 & 0xffff) + (sum2 >> 16);
+    (sum2 << 16) | sum1
+}
+
+enum Mask {
+    AllValid,
+    AllInvalid,
+    Bits(Vec<u8>),
+}
+
+impl Mask {
+    fn is_valid(&self, k: usize) -> bool {
+        match self {
+            Self::AllValid => true,
+            Self::AllInvalid => false,
+            Self::Bits(bits) => bits[k >> 3] & (128 >> (k & 7)) != 0,
+        }
+    }
+
+    // One byte per pixel, 0 is invalid
+    fn into_bytes(self, pixel_count: usize) -> Option<Vec<u8>> {
+        match self {
+            Self::AllValid => None,
+            Self::AllInvalid => Some(vec![0; pixel_count]),
+            Self::Bits(_) => Some(
+                (0..pixel_count)
+                    .map(|k| if self.is_valid(k) { 255 } else { 0 })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn read_mask(reader: &mut Reader, header: &Header) -> Result<Mask, DecompressError> {
+    let num_bytes = reader.i32()?;
+    let n = header.pixel_count();
+    if header.num_valid == 0 {
+        Ok(Mask::AllInvalid)
+    } else if header.num_valid == n {
+        Ok(Mask::AllValid)
+    } else if num_bytes > 0 {
+        let rle = reader.take(num_bytes as usize)?;
+        let bits = decode_rle(rle, n.div_ceil(8))?;
+        Ok(Mask::Bits(bits))
+    } else {
+        error("Missing mask")
+    }
+}
+
+// Counts are i16, positive for a literal run, negative for a repeated byte, -32768 to end
+fn decode_rle(bytes: &[u8], size: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut reader = Reader::new(bytes);
+    let mut output = Vec::with_capacity(size);
+    loop {
+        let count = reader.i16()?;
+        if count == i16::MIN {
+            break;
+        }
+        if count > 0 {
+            output.extend_from_slice(reader.take(count as usize)?);
+        } else {
+            let value = reader.u8()?;
+            output.extend(std::iter::repeat_n(value, count.unsigned_abs() as usize));
+        }
+        if output.len() > size {
+            return error("Mask overflow");
+        }
+    }
+    if output.len() != size {
+        return error("Bad mask size");
+    }
+    Ok(output)
+}
+
+fn read_values(
+    reader: &mut Reader,
+    header: &Header,
+    mask: &Mask,
+) -> Result<Vec<f64>, DecompressError> {
+    let depth = header.depth;
+    let mut data = vec![0.0; header.pixel_count() * depth];
+    if header.num_valid == 0 {
+        return Ok(data);
+    }
+
+    // Constant image
+    if header.z_min == header.z_max {
+        data.fill(header.z_min);
+        return Ok(data);
+    }
+
+    // Per depth ranges
+    let mut z_max = vec![header.z_max; depth];
+    if header.version >= 4 {
+        let z_min = (0..depth)
+            .map(|_| reader.value(header.data_type))
+            .collect::<Result<Vec<f64>, _>>()?;
+        z_max = (0..depth)
+            .map(|_| reader.value(header.data_type))
+            .collect::<Result<Vec<f64>, _>>()?;
+        if z_min == z_max {
+            for (i, v) in data.iter_mut().enumerate() {
+                *v = z_min[i % depth];
+            }
+            return Ok(data);
+        }
+    }
+
+    let one_sweep = reader.u8()? != 0;
+    if one_sweep {
+        for k in 0..header.pixel_count() {
+            if mask.is_valid(k) {
+                for m in 0..depth {
+                    data[k * depth + m] = reader.value(header.data_type)?;
+                }
+            }
+        }
+        return Ok(data);
+    }
+
+    let try_huffman_int = header.version >= 2
+        && matches!(header.data_type, DataType::Byte | DataType::Char)
+        && header.max_z_error == 0.5;
+    let try_huffman_float = header.version >= 6
+        && matches!(header.data_type, DataType::Float | DataType::Double)
+        && header.max_z_error == 0.0;
+    if try_huffman_int || try_huffman_float {
+        match reader.u8()? {
+            0 => {}
+            mode @ (1 | 2) if try_huffman_int => {
+                decode_huffman(reader, header, mask, mode == 1, &mut data)?;
+                return Ok(data);
+            }
+            3 if try_huffman_float => return decode_float_lossless(reader, header),
+            mode => return Err(DecompressError::LercModeNotSupported(mode)),
+        }
+    }
+
+    read_tiles(reader, header, mask, &z_max, &mut data)?;
+    Ok(data)
+}
+
+fn read_tiles(
+    reader: &mut Reader,
+    header: &Header,
+    mask: &Mask,
+    z_max: &[f64],
+    data: &mut [f64],
+) -> Result<(), DecompressError> {
+    let (width, height, depth) = (header.width, header.height, header.depth);
+    let block = header.micro_block_size;
+    let inv_scale = 2.0 * header.max_z_error;
+    for i0 in (0..height).step_by(block) {
+        let i1 = (i0 + block).min(height);
+        for j0 in (0..width).step_by(block) {
+            let j1 = (j0 + block).min(width);
+            for dim in 0..depth {
+                let flag = reader.u8()?;
+                let (check, diff) = if header.version >= 5 {
+                    ((flag >> 3) & 7 == ((j0 >> 3) & 7) as u8, flag & 4 != 0)
+                } else {
+                    ((flag >> 2) & 15 == ((j0 >> 3) & 15) as u8, false)
+                };
+                if !check || (diff && dim == 0) {
+                    return error("Tile integrity check failed");
+                }
+
+                let valid_pixels = (i0..i1)
+                    .flat_map(|i| (j0..j1).map(move |j| i * width + j))
+                    .filter(|k| mask.is_valid(*k));
+                let previous = |data: &[f64], m: usize| if diff { data[m - 1] } else { 0.0 };
+                match flag & 3 {
+                    // Constant zero
+                    2 => {
+                        for k in valid_pixels {
+                            let m = k * depth + dim;
+                            data[m] = previous(data, m);
+                        }
+                    }
+                    // Raw values
+                    0 => {
+                        for k in valid_pixels {
+                            data[k * depth + dim] = reader.value(header.data_type)?;
+                        }
+                    }
+                    mode => {
+                        let data_type = if diff && header.data_type < DataType::Int {
+                            DataType::Int
+                        } else {
+                            header.data_type
+                        };
+                        let offset = reader.value(data_type.reduced(flag >> 6)?)?;
+                        if mode == 3 {
+                            // Constant offset
+                            for k in valid_pixels {
+                                let m = k * depth + dim;
+                                data[m] = offset + previous(data, m);
+                            }
+                        } else {
+                            // Quantized and bit stuffed
+                            let count = (i1 - i0) * (j1 - j0);
+                            let quantized = decode_bit_stuffed(reader, count, header.version)?;
+                            let all_valid = quantized.len() == count;
+                            let pixels = (i0..i1)
+                                .flat_map(|i| (j0..j1).map(move |j| i * width + j))
+                                .filter(|k| all_valid || mask.is_valid(*k));
+                            let mut values = quantized.iter();
+                            for k in pixels {
+                                let Some(q) = values.next() else {
+                                    return error("Not enough values");
+                                };
+                                let m = k * depth + dim;
+                                let z = offset + *q as f64 * inv_scale + previous(data, m);
+                                data[m] = z.min(z_max[dim]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode_bit_stuffed(
+    reader: &mut Reader,
+    max_count: usize,
+    version: i32,
+) -> Result<Vec<u32>, DecompressError> {
+    let flag = reader.u8()?;
+    let count_size = match flag >> 6 {
+        0 => 4,
+        1 => 2,
+        2 => 1,
+        _ => return error("Bad element count size"),
+    };
+    let use_lut = flag & 32 != 0;
+    let bits = (flag & 31) as u32;
+    let count = reader.uint(count_size)? as usize;
+    if count > max_count {
+        return error("Too many elements");
+    }
+
+    if !use_lut {
+        return match bits {
+            0 => Ok(vec![0; count]),
+            _ => unstuff(reader, count, bits, version),
+        };
+    }
+
+    if bits == 0 {
+        return error("Bad lookup table");
+    }
+    let lut_size = reader.u8()? as usize;
+    if lut_size == 0 {
+        return error("Bad lookup table");
+    }
+    let mut lut = vec![0];
+    lut.extend(unstuff(reader, lut_size - 1, bits, version)?);
+    let index_bits = usize::BITS - (lut_size - 1).leading_zeros();
+    if index_bits == 0 {
+        return error("Bad lookup table");
+    }
+    unstuff(reader, count, index_bits, version)?
+        .into_iter()
+        .map(|i| lut.get(i as usize).copied())
+        .collect::<Option<Vec<u32>>>()
+        .ok_or(DecompressError::LercDecodeError("Bad lookup index".into()))
+}
+
+fn unstuff(
+    reader: &mut Reader,
+    count: usize,
+    bits: u32,
+    version: i32,
+) -> Result<Vec<u32>, DecompressError> {
+    let total_bits = count * bits as usize;
+    let bytes = reader.take(total_bits.div_ceil(8))?;
+    let mut values = Vec::with_capacity(count);
+    if version >= 3 {
+        // Least significant bit first
+        let mut pos = 0;
+        for _ in 0..count {
+            let mut value = 0;
+            for b in 0..bits as usize {
+                let bit = (bytes[(pos + b) >> 3] >> ((pos + b) & 7)) & 1;
+                value |= (bit as u32) << b;
+            }
+            values.push(value);
+            pos += bits as usize;
+        }
+    } else {
+        // Most significant bit first within little endian words, the tail word is left aligned
+        let tail = bytes.len() % 4;
+        let mut words = WordBits::new(bytes);
+        if tail > 0 {
+            words.tail_shift = 8 * (4 - tail) as u32;
+        }
+        for _ in 0..count {
+            values.push(words.read(bits));
+        }
+    }
+    Ok(values)
+}
+
+// Most significant bit first reader over little endian u32 words
+struct WordBits<'a> {
+    bytes: &'a [u8],
+    bit: usize,
+    tail_shift: u32,
+}
+
+impl<'a> WordBits<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            bit: 0,
+            tail_shift: 0,
+        }
+    }
+
+    fn word(&self, index: usize) -> u32 {
+        let start = index * 4;
+        let mut word = [0; 4];
+        for (i, w) in word.iter_mut().enumerate() {
+            *w = self.bytes.get(start + i).copied().unwrap_or(0);
+        }
+        let word = u32::from_le_bytes(word);
+        if start + 4 > self.bytes.len() {
+            word << self.tail_shift
+        } else {
+            word
+        }
+    }
+
+    fn read_bit(&mut self) -> u32 {
+        let bit = (self.word(self.bit / 32) >> (31 - (self.bit % 32))) & 1;
+        self.bit += 1;
+        bit
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        (0..bits).fold(0, |value, _| (value << 1) | self.read_bit())
+    }
+
+    fn words_used(&self) -> usize {
+        self.bit.div_ceil(32)
+    }
+}
+
+fn decode_huffman(
+    reader: &mut Reader,
+    header: &Header,
+    mask: &Mask,
+    delta: bool,
+    data: &mut [f64],
+) -> Result<(), DecompressError> {
+    let codes = read_code_table(reader, header.version)?;
+    let (width, height, depth) = (header.width, header.height, header.depth);
+    let signed = header.data_type == DataType::Char;
+    let offset = if signed { 128 } else { 0 };
+
+    // Values wrap around like the 8 bit type they represent
+    let wrap = |v: i32| -> f64 {
+        if signed {
+            ((v + 128).rem_euclid(256) - 128) as f64
+        } else {
+            v.rem_euclid(256) as f64
+        }
+    };
+
+    let mut bits = WordBits::new(reader.remaining());
+    let mut decode_one = || decode_symbol(&codes, &mut bits).map(|value| value as i32 - offset);
+
+    if delta {
+        for dim in 0..depth {
+            let mut previous = 0;
+            for i in 0..height {
+                for j in 0..width {
+                    let k = i * width + j;
+                    if !mask.is_valid(k) {
+                        continue;
+                    }
+                    let mut value = decode_one()?;
+                    if j > 0 && mask.is_valid(k - 1) {
+                        value += previous;
+                    } else if i > 0 && mask.is_valid(k - width) {
+                        value += data[(k - width) * depth + dim] as i32;
+                    } else {
+                        value += previous;
+                    }
+                    let value = wrap(value);
+                    data[k * depth + dim] = value;
+                    previous = value as i32;
+                }
+            }
+        }
+    } else {
+        for k in 0..header.pixel_count() {
+            if mask.is_valid(k) {
+                for m in 0..depth {
+                    data[k * depth + m] = wrap(decode_one()?);
+                }
+            }
+        }
+    }
+
+    // The encoder pads an extra word for lookahead
+    let used = (bits.words_used() + 1) * 4;
+    reader.take(used.min(reader.remaining().len()))?;
+    Ok(())
+}
+
+// Most significant bit first, codes are looked up by (length, code)
+fn decode_symbol(
+    codes: &HashMap<(u32, u32), usize>,
+    bits: &mut WordBits,
+) -> Result<usize, DecompressError> {
+    let mut code = 0;
+    for length in 1..=32 {
+        code = (code << 1) | bits.read_bit();
+        if let Some(value) = codes.get(&(length, code)) {
+            return Ok(*value);
+        }
+    }
+    error("Bad huffman code")
+}
+
+fn read_code_table(
+    reader: &mut Reader,
+    version: i32,
+) -> Result<HashMap<(u32, u32), usize>, DecompressError> {
+    let huffman_version = reader.i32()?;
+    let size = reader.i32()?;
+    let i0 = reader.i32()?;
+    let i1 = reader.i32()?;
+    if huffman_version < 2 || i0 < 0 || i0 >= i1 || size <= 0 || size > 1 << 15 || i1 > 2 * size {
+        return error("Bad huffman table");
+    }
+    let (size, i0, i1) = (size as usize, i0 as usize, i1 as usize);
+    let wrap = |i: usize| if i < size { i } else { i - size };
+
+    let lengths = decode_bit_stuffed(reader, i1 - i0, version)?;
+    if lengths.len() != i1 - i0 {
+        return error("Bad huffman table");
+    }
+
+    let mut bits = WordBits::new(reader.remaining());
+    let mut codes = HashMap::new();
+    for (i, length) in (i0..i1).zip(lengths) {
+        if length > 32 {
+            return error("Bad huffman code length");
+        }
+        if length > 0 {
+            codes.insert((length, bits.read(length)), wrap(i));
+        }
+    }
+    reader.take(bits.words_used() * 4)?;
+    Ok(codes)
+}
+
+// Lerc2 v6 lossless floats (fpl_* in the Lerc library)
+//   Each byte of the values is coded as its own plane, integrated up to 5 times (level) and
+//   compressed with Huffman or PackBits. Values are delta coded along rows, or along columns
+//   and rows (predictor), with exponent and mantissa added separately.
+fn decode_float_lossless(
+    reader: &mut Reader,
+    header: &Header,
+) -> Result<Vec<f64>, DecompressError> {
+    // Multiple depths are coded as a single slice with a column per depth
+    let (width, height) = match header.depth {
+        1 => (header.width, header.height),
+        depth => (depth, header.pixel_count()),
+    };
+    let count = width * height;
+    let unit_size = header.data_type.size();
+    let predictor = reader.u8()?;
+    if predictor > 2 {
+        return error("Bad predictor");
+    }
+
+    let mut units = vec![0; count * unit_size];
+    for _ in 0..unit_size {
+        let index = reader.u8()? as usize;
+        let level = reader.u8()? as usize;
+        let size = reader.uint(4)? as usize;
+        if index >= unit_size || level > 5 {
+            return error("Bad byte plane");
+        }
+        let mut plane = decode_byte_plane(reader.take(size)?, count)?;
+        for start in (1..=level).rev() {
+            for i in start..count {
+                plane[i] = plane[i].wrapping_add(plane[i - 1]);
+            }
+        }
+        for (unit, byte) in units.chunks_exact_mut(unit_size).zip(plane) {
+            unit[index] = byte;
+        }
+    }
+
+    Ok(match header.data_type {
+        DataType::Float => {
+            let mut values: Vec<u32> = units
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            restore_deltas(&mut values, width, predictor, |a, b| {
+                ((a >> 23).wrapping_add(b >> 23) << 23) | (a.wrapping_add(b) & 0x7f_ffff)
+            });
+            // Exponent was moved in front of the sign
+            values
+                .into_iter()
+                .map(|v| ((v >> 24) << 23) | ((v >> 23) & 1) << 31 | (v & 0x7f_ffff))
+                .map(|v| f32::from_bits(v) as f64)
+                .collect()
+        }
+        _ => {
+            let mut values: Vec<u64> = units
+                .chunks_exact(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()))
+                .collect();
+            restore_deltas(&mut values, width, predictor, |a, b| {
+                let mantissa = (1 << 52) - 1;
+                ((a >> 52).wrapping_add(b >> 52) << 52) | (a.wrapping_add(b) & mantissa)
+            });
+            values.into_iter().map(f64::from_bits).collect()
+        }
+    })
+}
+
+// Predictor 1 is a delta along rows, 2 is a delta along columns and then rows
+fn restore_deltas<T: Copy>(values: &mut [T], width: usize, predictor: u8, add: fn(T, T) -> T) {
+    if predictor == 0 || width == 0 {
+        return;
+    }
+    if predictor == 2 {
+        for i in width..values.len() {
+            values[i] = add(values[i], values[i - width]);
+        }
+    }
+    for row in values.chunks_exact_mut(width) {
+        for x in 1..width {
+            row[x] = add(row[x], row[x - 1]);
+        }
+    }
+}
+
+fn decode_byte_plane(bytes: &[u8], count: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut reader = Reader::new(bytes);
+    match reader.u8()? {
+        // Huffman, with the code table bit stuffed as in Lerc2 v5
+        0 => {
+            let codes = read_code_table(&mut reader, 5)?;
+            let mut bits = WordBits::new(reader.remaining());
+            (0..count)
+                .map(|_| decode_symbol(&codes, &mut bits).map(|value| value as u8))
+                .collect()
+        }
+        // Constant
+        1 => {
+            let value = reader.u8()?;
+            if reader.uint(4)? as usize != count {
+                return error("Bad byte plane size");
+            }
+            Ok(vec![value; count])
+        }
+        // Raw
+        2 => Ok(reader.take(count)?.to_vec()),
+        // Header byte n: 0..=127 copies the next n + 1 bytes, 128..=255 repeats the next byte n - 126 times
+        3 => {
+            let mut plane = Vec::with_capacity(count);
+            while !reader.remaining().is_empty() {
+                let n = reader.u8()? as usize;
+                match n {
+                    0..=127 => plane.extend_from_slice(reader.take(n + 1)?),
+                    _ => plane.extend(std::iter::repeat_n(reader.u8()?, n - 126)),
+                }
+                if plane.len() > count {
+                    return error("Byte plane overflow");
+                }
+            }
+            if plane.len() != count {
+                return error("Bad byte plane size");
+            }
+            Ok(plane)
+        }
+        _ => error("Bad byte plane mode"),
+    }
+}
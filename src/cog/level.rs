@@ -1,34 +1,72 @@
 use super::compression::{Compression, Predictor};
+use super::deferred::DeferredTiles;
+use super::lerc::{self, LercParameters};
+use super::nodata::NoData;
 use super::CloudTiffError;
-use crate::raster::{ExtraSamples, PhotometricInterpretation, Raster, SampleFormat};
-use crate::tiff::{Endian, Ifd, TagId, TiffError};
-use crate::{Region, UnitFloat};
+use super::{jpeg, webp};
+use crate::raster::{
+    swap_bits, ExtraSamples, PhotometricInterpretation, PlanarConfiguration, Raster, SampleFormat,
+};
+use crate::tiff::{Endian, Ifd, TagId, TiffError, TiffLimits};
+use crate::{ReadRange, Region, UnitFloat};
 use std::fmt::Display;
+use std::ops::Range;
 
 #[derive(Clone, Debug)]
 pub struct Level {
     pub overview: Option<usize>,
+    pub subfile_type: u32,
     pub dimensions: (u32, u32),
     pub tile_width: u32,
     pub tile_height: u32,
     pub compression: Compression,
     pub predictor: Predictor,
     pub interpretation: PhotometricInterpretation,
+    pub planar_configuration: PlanarConfiguration,
     pub bits_per_sample: Vec<u16>,
     pub sample_format: Vec<SampleFormat>,
     pub extra_samples: Vec<ExtraSamples>,
     pub endian: Endian,
     pub offsets: Vec<u64>,
     pub byte_counts: Vec<usize>,
+    pub deferred: Option<DeferredTiles>, // replaces offsets and byte_counts when not read at open
+    pub jpeg_tables: Option<Vec<u8>>,
+    pub ycbcr_subsampling: (u16, u16),
+    pub lerc_parameters: Option<LercParameters>,
+    pub nodata: Option<NoData>,
+    pub color_map: Option<Vec<u16>>,
+    pub reference_black_white: Option<[f64; 6]>,
+    pub mask: Option<Box<Level>>, // GDAL internal transparency mask
+    pub limits: TiffLimits,       // of the file, bounds decoded tile sizes
+    pub strips: bool,             // strips are read as full width tiles, RowsPerStrip tall
 }
 
 impl Level {
     pub fn from_ifd(ifd: &Ifd, endian: Endian) -> Result<Self, CloudTiffError> {
+        let subfile_type = ifd.get_tag_value(TagId::SubfileType).unwrap_or(0);
+
         // Required tags
         let width = ifd.get_tag_value(TagId::ImageWidth)?;
-        let height = ifd.get_tag_value(TagId::ImageHeight)?;
-        let tile_width = ifd.get_tag_value(TagId::TileWidth)?;
-        let tile_height = ifd.get_tag_value(TagId::TileLength)?;
+        let height: u32 = ifd.get_tag_value(TagId::ImageHeight)?;
+
+        // Strips are full width tiles, the last one can be cut short
+        let strips = ifd.get_tag(TagId::TileWidth).is_err();
+        let (tile_width, tile_height, offsets_id, byte_counts_id) = match strips {
+            false => (
+                ifd.get_tag_value(TagId::TileWidth)?,
+                ifd.get_tag_value(TagId::TileLength)?,
+                TagId::TileOffsets,
+                TagId::TileByteCounts,
+            ),
+            true => (
+                width,
+                ifd.get_tag_value::<u32>(TagId::RowsPerStrip)
+                    .unwrap_or(u32::MAX) // TIFF default, a single strip
+                    .clamp(1, height.max(1)),
+                TagId::StripOffsets,
+                TagId::StripByteCounts,
+            ),
+        };
         let compression = ifd.get_tag_value::<u16>(TagId::Compression)?.into();
         let predictor = ifd
             .get_tag_value::<u16>(TagId::Predictor)
@@ -55,29 +93,191 @@ impl Level {
             .get_tag_value::<u16>(TagId::PhotometricInterpretation)
             .unwrap_or(PhotometricInterpretation::Unknown.into())
             .into();
-        let offsets = ifd.get_tag_values(TagId::TileOffsets)?;
-        let byte_counts = ifd.get_tag_values(TagId::TileByteCounts)?;
+        let planar_configuration = ifd
+            .get_tag_value::<u16>(TagId::PlanarConfiguration)
+            .unwrap_or(PlanarConfiguration::Chunky.into())
+            .into();
+        let jpeg_tables = ifd
+            .get_tag(TagId::JPEGTables)
+            .ok()
+            .map(|tag| tag.data.clone());
+        let ycbcr_subsampling = match ifd.get_tag_values::<u16>(TagId::YCbCrSubSampling) {
+            Ok(v) if v.len() == 2 => (v[0], v[1]),
+            _ => (2, 2), // TIFF default
+        };
+        let lerc_parameters = ifd
+            .get_tag_values::<u32>(TagId::LercParameters)
+            .ok()
+            .map(|v| LercParameters::from_values(&v));
+        let nodata = ifd.get_tag(TagId::GDALNoData).ok().and_then(|tag| {
+            let format = sample_format
+                .first()
+                .copied()
+                .unwrap_or(SampleFormat::Unsigned);
+            NoData::parse(&tag.as_string_lossy(), format)
+        });
+        let color_map: Option<Vec<u16>> = ifd.get_tag_values(TagId::ColorMap).ok();
+        let reference_black_white = ifd
+            .get_tag_values::<f64>(TagId::ReferenceBlackWhite)
+            .ok()
+            .and_then(|v| v.try_into().ok());
+        let offsets_tag = ifd.get_tag(offsets_id)?;
+        let byte_counts_tag = ifd.get_tag(byte_counts_id)?;
+        let entry_count = offsets_tag.count;
 
-        if offsets.len() != byte_counts.len() {
-            return Err(CloudTiffError::BadTiff(TiffError::BadTag(
-                TagId::TileOffsets,
-            )));
+        if entry_count != byte_counts_tag.count {
+            return Err(CloudTiffError::BadTiff(TiffError::BadTag(offsets_id)));
         }
 
-        Ok(Self {
+        // Deferred tile entries are fetched as tiles are read
+        let planes = match planar_configuration {
+            PlanarConfiguration::Planar => bits_per_sample.len(),
+            _ => 1,
+        };
+        let deferred = DeferredTiles::from_tags(offsets_tag, byte_counts_tag, planes);
+        let (offsets, byte_counts) = match deferred {
+            Some(_) => (vec![], vec![]),
+            None => (
+                ifd.get_tag_values(offsets_id)?,
+                ifd.get_tag_values(byte_counts_id)?,
+            ),
+        };
+
+        let level = Self {
             overview: None,
+            subfile_type,
             dimensions: (width, height),
             tile_width,
             tile_height,
             compression,
             predictor,
             interpretation,
+            planar_configuration,
             bits_per_sample,
             sample_format,
             extra_samples,
             endian,
             offsets,
             byte_counts,
+            deferred,
+            jpeg_tables,
+            ycbcr_subsampling,
+            lerc_parameters,
+            nodata,
+            color_map,
+            reference_black_white,
+            mask: None,
+            limits: TiffLimits::default(),
+            strips,
+        };
+
+        // Palettes have an RGB entry for every value of the first sample
+        if let Some(color_map) = &level.color_map {
+            let bits = level.bits_per_sample.first().copied().unwrap_or(0);
+            if bits > 16 || color_map.len() != 3 << bits {
+                return Err(CloudTiffError::BadTiff(TiffError::BadTag(TagId::ColorMap)));
+            }
+        }
+
+        // Extra samples are the last samples of each pixel
+        if level.extra_samples.len() > level.bits_per_sample.len() {
+            return Err(CloudTiffError::BadTiff(TiffError::BadTag(
+                TagId::ExtraSamples,
+            )));
+        }
+
+        // Planar levels hold every tile once per plane
+        if entry_count < level.tile_count() * level.plane_count() {
+            return Err(CloudTiffError::BadTiff(TiffError::BadTag(offsets_id)));
+        }
+
+        Ok(level)
+    }
+
+    /// Internal transparency masks have NewSubfileType bit 4 set
+    pub fn is_mask(&self) -> bool {
+        self.subfile_type & 4 != 0
+    }
+
+    /// Number of separately stored sample planes, 1 unless planar
+    pub fn plane_count(&self) -> usize {
+        match self.planar_configuration {
+            PlanarConfiguration::Planar => self.bits_per_sample.len(),
+            _ => 1,
+        }
+    }
+
+    /// Number of tile positions, regardless of planes
+    pub fn tile_count(&self) -> usize {
+        self.col_count() * self.row_count()
+    }
+
+    /// Level view that only includes the selected bands, for fetching fewer planes
+    pub fn select_bands(&self, bands: &[usize]) -> Result<Self, CloudTiffError> {
+        if self.planar_configuration != PlanarConfiguration::Planar {
+            return Err(CloudTiffError::NotSupported(
+                "Band selection requires planar configuration".into(),
+            ));
+        }
+        let samples = self.bits_per_sample.len();
+        if bands.is_empty() || bands.iter().any(|band| *band >= samples) {
+            return Err(CloudTiffError::NotSupported(format!(
+                "Bands {bands:?} not within {samples} samples"
+            )));
+        }
+
+        let planes = |band: &usize| band * self.tile_count()..(band + 1) * self.tile_count();
+        let first_extra =
+            samples
+                .checked_sub(self.extra_samples.len())
+                .ok_or(CloudTiffError::BadTiff(TiffError::BadTag(
+                    TagId::ExtraSamples,
+                )))?;
+        let interpretation = match bands.len() {
+            n if n == samples => self.interpretation,
+            1 => PhotometricInterpretation::BlackIsZero,
+            _ => PhotometricInterpretation::Unknown,
+        };
+
+        // Deferred entries are looked up in the file's planes
+        let deferred = self.deferred.clone().map(|deferred| DeferredTiles {
+            planes: bands.iter().map(|b| deferred.planes[*b]).collect(),
+            ..deferred
+        });
+
+        Ok(Self {
+            interpretation,
+            bits_per_sample: bands.iter().map(|b| self.bits_per_sample[*b]).collect(),
+            sample_format: bands
+                .iter()
+                .map(|b| {
+                    self.sample_format
+                        .get(*b)
+                        .copied()
+                        .unwrap_or(SampleFormat::Unsigned)
+                })
+                .collect(),
+            extra_samples: bands
+                .iter()
+                .filter(|b| **b >= first_extra)
+                .map(|b| self.extra_samples[*b - first_extra])
+                .collect(),
+            offsets: match deferred {
+                Some(_) => vec![],
+                None => bands
+                    .iter()
+                    .flat_map(|b| self.offsets[planes(b)].to_vec())
+                    .collect(),
+            },
+            byte_counts: match deferred {
+                Some(_) => vec![],
+                None => bands
+                    .iter()
+                    .flat_map(|b| self.byte_counts[planes(b)].to_vec())
+                    .collect(),
+            },
+            deferred,
+            ..self.clone()
         })
     }
 
@@ -146,9 +346,21 @@ impl Level {
         (col, row)
     }
 
+    /// Byte range of a tile's first plane
     pub fn tile_byte_range(&self, index: usize) -> Result<(u64, u64), CloudTiffError> {
+        self.plane_byte_range(index, 0)
+    }
+
+    /// Byte ranges of a tile, one per plane
+    pub fn tile_byte_ranges(&self, index: usize) -> Result<Vec<(u64, u64)>, CloudTiffError> {
+        (0..self.plane_count())
+            .map(|plane| self.plane_byte_range(index, plane))
+            .collect()
+    }
+
+    fn plane_byte_range(&self, index: usize, plane: usize) -> Result<(u64, u64), CloudTiffError> {
         // Validate index
-        let max_valid_index = self.offsets.len().min(self.byte_counts.len()) - 1;
+        let max_valid_index = self.tile_count().saturating_sub(1);
         if index > max_valid_index {
             return Err(CloudTiffError::TileIndexOutOfRange((
                 index,
@@ -157,37 +369,477 @@ impl Level {
         }
 
         // Lookup byte range
-        let offset = self.offsets[index];
-        let byte_count = self.byte_counts[index];
+        let (start, end) = match &self.deferred {
+            Some(deferred) => deferred.get(index, plane, self.tile_count())?,
+            None => {
+                let i = plane * self.tile_count() + index;
+                let (Some(offset), Some(byte_count)) =
+                    (self.offsets.get(i), self.byte_counts.get(i))
+                else {
+                    return Err(CloudTiffError::TileIndexOutOfRange((
+                        i,
+                        self.offsets.len().min(self.byte_counts.len()),
+                    )));
+                };
+                let offsets_id = match self.strips {
+                    true => TagId::StripOffsets,
+                    false => TagId::TileOffsets,
+                };
+                let end = offset
+                    .checked_add(*byte_count as u64)
+                    .ok_or(CloudTiffError::BadTiff(TiffError::BadTag(offsets_id)))?;
+                (*offset, end)
+            }
+        };
+
+        // Tile bytes are read whole before decoding
+        if end - start > self.limits.max_compressed_tile_bytes {
+            return Err(CloudTiffError::BadTiff(TiffError::CompressedTileTooLarge(
+                end - start,
+            )));
+        }
+        Ok((start, end))
+    }
+
+    /// File byte ranges of deferred tile entries that must be loaded to read these tiles
+    pub fn missing_tile_entries(&self, indices: &[usize]) -> Vec<Range<u64>> {
+        let mut ranges = match &self.deferred {
+            Some(deferred) => deferred.missing_ranges(indices, self.tile_count()),
+            None => vec![],
+        };
+        if let Some(mask) = &self.mask {
+            ranges.extend(mask.missing_tile_entries(indices));
+        }
+        ranges
+    }
+
+    /// Load deferred tile entries from bytes read at the start of a missing_tile_entries range
+    pub fn provide_tile_entries(&self, start: u64, bytes: &[u8]) -> Result<(), CloudTiffError> {
+        if let Some(deferred) = &self.deferred {
+            deferred.provide(start, bytes)?;
+        }
+        if let Some(mask) = &self.mask {
+            mask.provide_tile_entries(start, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Fetch the deferred tile entries of these tiles, a no-op when they are already loaded
+    ///   Entries are kept by the level (and its clones) for later reads
+    pub fn load_tile_entries<R: ReadRange>(
+        &self,
+        reader: &R,
+        indices: &[usize],
+    ) -> Result<(), CloudTiffError> {
+        for range in self.missing_tile_entries(indices) {
+            let bytes = reader.read_range_to_vec(range.start, range.end)?;
+            self.provide_tile_entries(range.start, &bytes)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn load_tile_entries_async<R: crate::AsyncReadRange>(
+        &self,
+        reader: &R,
+        indices: &[usize],
+    ) -> Result<(), CloudTiffError> {
+        let reads = self
+            .missing_tile_entries(indices)
+            .into_iter()
+            .map(|range| async move {
+                reader
+                    .read_range_to_vec_async(range.start, range.end)
+                    .await
+                    .map(|bytes| (range.start, bytes))
+            });
+        for (start, bytes) in futures::future::try_join_all(reads).await? {
+            self.provide_tile_entries(start, &bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Sparse tiles (GDAL SPARSE_OK) have no data in the file
+    pub fn is_sparse(&self, index: usize) -> bool {
+        self.tile_byte_ranges(index)
+            .is_ok_and(|ranges| ranges.iter().all(|(start, end)| start == end))
+    }
+
+    /// Nodata (or zero) filled tile with every pixel masked out, used for sparse tiles
+    pub fn sparse_tile(&self) -> Result<Raster, CloudTiffError> {
+        let pixel_count = self.tile_width as usize * self.tile_height as usize;
+        let mut raster = Raster::blank(
+            (self.tile_width, self.tile_height),
+            self.bits_per_sample.clone(),
+            self.tile_interpretation(),
+            self.sample_format.clone(),
+            self.extra_samples.clone(),
+        );
+        let fill: Option<Vec<u8>> = self.nodata.and_then(|nodata| {
+            self.bits_per_sample
+                .iter()
+                .map(|bits| nodata.to_bytes(*bits))
+                .collect::<Option<Vec<Vec<u8>>>>()
+                .map(|samples| samples.concat())
+        });
+        if let Some(pixel) = fill.filter(|pixel| !pixel.is_empty()) {
+            raster
+                .buffer
+                .chunks_exact_mut(pixel.len())
+                .for_each(|chunk| chunk.copy_from_slice(&pixel));
+        }
+        self.attach_photometrics(raster.with_mask(vec![0; pixel_count])?)
+    }
 
-        Ok((offset, offset + byte_count as u64))
+    /// Attach the level's palette and YCbCr reference to a raster of its tiles
+    pub fn attach_photometrics(&self, mut raster: Raster) -> Result<Raster, CloudTiffError> {
+        raster.reference_black_white = self.reference_black_white;
+        match &self.color_map {
+            Some(color_map) => Ok(raster.with_color_map(color_map.clone())?),
+            None => Ok(raster),
+        }
+    }
+
+    /// Mark pixels whose samples all equal the nodata value as invalid
+    fn apply_nodata(&self, buffer: &[u8], mask: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        let Some(nodata) = self.nodata else {
+            return mask;
+        };
+        if self.bits_per_sample.iter().any(|bits| bits % 8 != 0) {
+            return Some(self.apply_packed_nodata(nodata, buffer, mask));
+        }
+        let sizes: Vec<usize> = self
+            .bits_per_sample
+            .iter()
+            .map(|b| *b as usize / 8)
+            .collect();
+        let pixel_size: usize = sizes.iter().sum();
+        if pixel_size == 0 {
+            return mask;
+        }
+        let pixel_count = buffer.len() / pixel_size;
+        let mut mask = mask.unwrap_or_else(|| vec![255; pixel_count]);
+        for (valid, pixel) in mask.iter_mut().zip(buffer.chunks_exact(pixel_size)) {
+            let mut offset = 0;
+            let is_nodata = sizes.iter().all(|size| {
+                let sample = &pixel[offset..offset + size];
+                offset += size;
+                nodata.matches(sample)
+            });
+            if is_nodata {
+                *valid = 0;
+            }
+        }
+        Some(mask)
+    }
+
+    // Packed samples are read most significant bit first, each row starts on a byte boundary
+    fn apply_packed_nodata(&self, nodata: NoData, buffer: &[u8], mask: Option<Vec<u8>>) -> Vec<u8> {
+        let width = self.tile_width as usize;
+        let pixel_count = width * self.tile_height as usize;
+        let pixel_bits: usize = self.bits_per_sample.iter().map(|b| *b as usize).sum();
+        let row_bits = (width * pixel_bits).div_ceil(8) * 8;
+        let mut mask = mask.unwrap_or_else(|| vec![255; pixel_count]);
+        for (i, valid) in mask.iter_mut().enumerate() {
+            let mut position = (i / width) * row_bits + (i % width) * pixel_bits;
+            let is_nodata = self.bits_per_sample.iter().all(|bits| {
+                let mut value = 0u64;
+                for _ in 0..*bits {
+                    let bit = buffer
+                        .get(position / 8)
+                        .map_or(0, |byte| (byte >> (7 - position % 8)) & 1);
+                    value = (value << 1) | bit as u64;
+                    position += 1;
+                }
+                nodata.matches_packed(value, *bits)
+            });
+            if is_nodata {
+                *valid = 0;
+            }
+        }
+        mask
+    }
+
+    /// Extract a tile and apply the matching tile of the mask level, if any
+    pub fn extract_masked_tile<B: AsRef<[u8]>>(
+        &self,
+        planes: &[B],
+        mask: Option<&[u8]>,
+    ) -> Result<Raster, CloudTiffError> {
+        let tile = self.extract_tile_from_planes(planes)?;
+        let (Some(mask_level), Some(mask)) = (&self.mask, mask) else {
+            return Ok(tile);
+        };
+        let mut mask = mask_level.extract_mask_from_bytes(mask)?;
+        if let Some(tile_mask) = &tile.mask {
+            mask.iter_mut()
+                .zip(tile_mask)
+                .for_each(|(a, b)| *a = (*a).min(*b));
+        }
+        Ok(tile.with_mask(mask)?)
+    }
+
+    /// Decode a transparency mask tile into one byte per pixel, 0 is invalid
+    pub fn extract_mask_from_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>, CloudTiffError> {
+        let (width, height) = (self.tile_width as usize, self.tile_height as usize);
+
+        // Sparse mask tiles are fully transparent
+        if bytes.is_empty() {
+            return Ok(vec![0; width * height]);
+        }
+
+        let (buffer, _) = self.decode_plane(bytes, &self.bits_per_sample)?;
+        let mask: Vec<u8> = match self.bits_per_sample.as_slice() {
+            [1] => buffer
+                .chunks(width.div_ceil(8))
+                .flat_map(|row| {
+                    (0..width).map(|i| match row[i / 8] & (0x80 >> (i % 8)) {
+                        0 => 0,
+                        _ => 255,
+                    })
+                })
+                .collect(),
+            [8] => buffer,
+            bits => {
+                return Err(CloudTiffError::NotSupported(format!(
+                    "Mask bits per sample {bits:?}"
+                )))
+            }
+        };
+        if mask.len() != width * height {
+            return Err(CloudTiffError::NotSupported(format!(
+                "Mask tile has {} pixels, expected {width}x{height}",
+                mask.len()
+            )));
+        }
+        Ok(mask)
     }
 
     pub fn extract_tile_from_bytes(&self, bytes: &[u8]) -> Result<Raster, CloudTiffError> {
+        self.extract_tile_from_planes(&[bytes])
+    }
+
+    /// Extract a tile from the bytes of each of its planes, interleaving planar samples
+    pub fn extract_tile_from_planes<B: AsRef<[u8]>>(
+        &self,
+        planes: &[B],
+    ) -> Result<Raster, CloudTiffError> {
+        if planes.len() != self.plane_count() {
+            return Err(CloudTiffError::NotSupported(format!(
+                "Expected {} planes, got {}",
+                self.plane_count(),
+                planes.len()
+            )));
+        }
+
+        let (buffer, mask) = if planes.len() == 1 {
+            let (buffer, mask) = self.decode_plane(planes[0].as_ref(), &self.bits_per_sample)?;
+            (self.upsample_ycbcr(buffer)?, mask)
+        } else {
+            let pixel_count = self.tile_width as usize * self.tile_height as usize;
+            let mut decoded = vec![];
+            let mut mask: Option<Vec<u8>> = None;
+            for (bytes, bits) in planes.iter().zip(&self.bits_per_sample) {
+                if bits % 8 != 0 {
+                    return Err(CloudTiffError::NotSupported(format!(
+                        "Planar samples must be byte aligned: {bits} bits"
+                    )));
+                }
+                let (buffer, plane_mask) = self.decode_plane(bytes.as_ref(), &[*bits])?;
+                if buffer.len() != pixel_count * (*bits as usize / 8) {
+                    return Err(CloudTiffError::NotSupported(format!(
+                        "Plane has {} bytes, expected {pixel_count} samples of {bits} bits",
+                        buffer.len()
+                    )));
+                }
+                // A pixel is invalid if any of its planes is
+                mask = match (mask, plane_mask) {
+                    (Some(a), Some(b)) => Some(a.iter().zip(b).map(|(a, b)| b.min(*a)).collect()),
+                    (a, b) => a.or(b),
+                };
+                decoded.push(buffer);
+            }
+
+            // Interleave
+            let sizes: Vec<usize> = self
+                .bits_per_sample
+                .iter()
+                .map(|b| *b as usize / 8)
+                .collect();
+            let mut buffer = Vec::with_capacity(pixel_count * sizes.iter().sum::<usize>());
+            for i in 0..pixel_count {
+                for (plane, size) in decoded.iter().zip(&sizes) {
+                    buffer.extend_from_slice(&plane[i * size..(i + 1) * size]);
+                }
+            }
+            (buffer, mask)
+        };
+        let mask = self.apply_nodata(&buffer, mask);
+
+        // Rasterization
+        let raster = Raster::new(
+            (self.tile_width, self.tile_height),
+            buffer,
+            self.bits_per_sample.clone(),
+            self.tile_interpretation(),
+            self.sample_format.clone(),
+            self.extra_samples.clone(),
+        )?;
+        let raster = match mask {
+            Some(mask) => raster.with_mask(mask)?,
+            None => raster,
+        };
+        self.attach_photometrics(raster)
+    }
+
+    // Subsampled YCbCr is stored in data units of h*v luma samples followed by Cb and Cr,
+    //   unpack to one Y, Cb, Cr triplet per pixel
+    fn upsample_ycbcr(&self, buffer: Vec<u8>) -> Result<Vec<u8>, CloudTiffError> {
+        let (h, v) = (
+            self.ycbcr_subsampling.0 as usize,
+            self.ycbcr_subsampling.1 as usize,
+        );
+        if self.tile_interpretation() != PhotometricInterpretation::YCbCr || (h, v) == (1, 1) {
+            return Ok(buffer);
+        }
+        if self.bits_per_sample != [8, 8, 8] || ![1, 2, 4].contains(&h) || ![1, 2, 4].contains(&v) {
+            return Err(CloudTiffError::NotSupported(format!(
+                "YCbCr subsampling {h}x{v} with bits per sample {:?}",
+                self.bits_per_sample
+            )));
+        }
+
+        let (width, height) = (self.tile_width as usize, self.tile_height as usize);
+        let unit_size = h * v + 2;
+        let units_per_row = width.div_ceil(h);
+        if buffer.len() < units_per_row * height.div_ceil(v) * unit_size {
+            return Err(CloudTiffError::NotSupported(format!(
+                "YCbCr tile has {} bytes, expected {}x{} data units of {unit_size}",
+                buffer.len(),
+                units_per_row,
+                height.div_ceil(v)
+            )));
+        }
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let unit = &buffer[((y / v) * units_per_row + x / h) * unit_size..];
+                pixels.extend_from_slice(&[
+                    unit[(y % v) * h + x % h],
+                    unit[h * v],
+                    unit[h * v + 1],
+                ]);
+            }
+        }
+        Ok(pixels)
+    }
+
+    fn decode_plane(
+        &self,
+        bytes: &[u8],
+        bits_per_sample: &[u16],
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>), CloudTiffError> {
+        let samples = bits_per_sample.len();
+        // The predictor needs every sample at the same bit depth
+        self.predictor.check(bits_per_sample, &self.sample_format)?;
+        let bit_depth = bits_per_sample[0] as usize;
+
+        // Tile dimensions come from the file, check before decoding
+        let max_bytes = self.limits.max_tile_bytes;
+        let pixel_bits: usize = bits_per_sample.iter().map(|b| *b as usize).sum();
+        let size = (self.tile_width as usize)
+            .checked_mul(pixel_bits)
+            .and_then(|bits| bits.div_ceil(8).checked_mul(self.tile_height as usize));
+        if size.is_none_or(|size| size > max_bytes) {
+            return Err(CloudTiffError::BadTiff(TiffError::TileTooLarge(max_bytes)));
+        }
+
         // Decompression
-        let mut buffer = self.compression.decode(bytes)?;
+        let mut mask = None;
+        let mut buffer = match self.compression {
+            Compression::Jpeg => {
+                let interpretation = match samples {
+                    1 => PhotometricInterpretation::BlackIsZero,
+                    _ => self.interpretation,
+                };
+                let (buffer, dimensions) = jpeg::decode(
+                    bytes,
+                    self.jpeg_tables.as_deref(),
+                    interpretation,
+                    self.endian,
+                    max_bytes,
+                )?;
+                if !self.fits_tile(dimensions) {
+                    return Err(CloudTiffError::NotSupported(format!(
+                        "JPEG tile dimensions {dimensions:?} do not match level tile size"
+                    )));
+                }
+                buffer
+            }
+            Compression::WebP => {
+                let (buffer, dimensions) = webp::decode(bytes, samples, max_bytes)?;
+                if !self.fits_tile(dimensions) {
+                    return Err(CloudTiffError::NotSupported(format!(
+                        "WebP tile dimensions {dimensions:?} do not match level tile size"
+                    )));
+                }
+                buffer
+            }
+            Compression::ESRILerc => {
+                let parameters = self.lerc_parameters.unwrap_or_default();
+                let blob = lerc::decode(bytes, &parameters, self.endian, max_bytes)?;
+                if !self.fits_tile(blob.dimensions) || blob.depth != samples {
+                    return Err(CloudTiffError::NotSupported(format!(
+                        "LERC tile {:?}x{} does not match level tile size",
+                        blob.dimensions, blob.depth
+                    )));
+                }
+                mask = blob.mask;
+                blob.buffer
+            }
+            compression => compression.decode_with_limit(bytes, max_bytes)?,
+        };
 
-        // Todo, De-endian
+        // The last strip only holds the remaining rows of the image
+        if let (true, Some(size)) = (self.strips, size) {
+            if buffer.len() < size {
+                buffer.resize(size, 0);
+            }
+            let pixel_count = self.tile_width as usize * self.tile_height as usize;
+            if let Some(mask) = mask.as_mut().filter(|mask| mask.len() < pixel_count) {
+                mask.resize(pixel_count, 0);
+            }
+        }
 
         // Predictor
-        let bit_depth = self.bits_per_sample[0] as usize; // TODO not all samples are necessarily the same bit depth
         self.predictor.predict(
             buffer.as_mut_slice(),
             self.tile_width as usize,
             bit_depth,
-            self.bits_per_sample.len(),
+            samples,
+            self.endian,
         )?;
 
-        // Rasterization
-        Ok(Raster::new(
-            (self.tile_width, self.tile_height),
-            buffer,
-            self.bits_per_sample.clone(),
-            self.interpretation,
-            self.sample_format.clone(),
-            self.extra_samples.clone(),
-            self.endian, // TODO shouldn't need this
-        )?)
+        // Rasters are native endian
+        let swap_bits = swap_bits(bits_per_sample, &self.sample_format);
+        self.endian.swap_samples(&mut buffer, &swap_bits);
+
+        Ok((buffer, mask))
+    }
+
+    // Decoded dimensions must match the tile, strips can be shorter
+    fn fits_tile(&self, (width, height): (u32, u32)) -> bool {
+        width == self.tile_width
+            && (height == self.tile_height || self.strips && height < self.tile_height)
+    }
+
+    /// Photometric interpretation of extracted tiles, which can differ from the file's
+    pub fn tile_interpretation(&self) -> PhotometricInterpretation {
+        match (self.compression, self.interpretation) {
+            // JPEG decoder converts YCbCr to RGB
+            (Compression::Jpeg, PhotometricInterpretation::YCbCr) => PhotometricInterpretation::RGB,
+            (_, interpretation) => interpretation,
+        }
     }
 
     pub fn tile_bounds(&self, index: &usize) -> (f64, f64, f64, f64) {
@@ -217,7 +869,7 @@ impl Display for Level {
             "Level({}x{}, {} tiles, {:?} Compression, {:?} Predictor)",
             self.dimensions.0,
             self.dimensions.1,
-            self.offsets.len(),
+            self.tile_count() * self.plane_count(),
             self.compression,
             self.predictor
         )
@@ -0,0 +1,265 @@
+// GDAL_METADATA (Tag 42112)
+// https://gdal.org/en/latest/drivers/raster/gtiff.html#metadata
+//   ASCII encoded XML of the form
+//   <GDALMetadata>
+//     <Item name="STATISTICS_MAXIMUM" sample="0">255</Item>
+//     <Item name="SCALE" sample="0" role="scale">0.01</Item>
+//   </GDALMetadata>
+//   Items without a sample apply to the dataset, items with a sample apply to that band.
+
+use super::{CloudTiffError, CloudTiffResult};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GdalMetadata {
+    pub items: Vec<MetadataItem>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataItem {
+    pub name: String,
+    pub value: String,
+    pub sample: Option<usize>,
+    pub role: Option<String>,
+    pub domain: Option<String>,
+}
+
+impl GdalMetadata {
+    pub fn parse(xml: &str) -> CloudTiffResult<Self> {
+        let bad = |msg: &str| CloudTiffError::BadMetadata(msg.to_string());
+        if !xml.contains("<GDALMetadata") {
+            return Err(bad("Missing <GDALMetadata> element"));
+        }
+
+        let mut items = vec![];
+        let mut rest = xml;
+        while let Some(start) = rest.find("<Item") {
+            rest = &rest[start + "<Item".len()..];
+            let tag_end = rest.find('>').ok_or_else(|| bad("Unterminated <Item>"))?;
+            let (attributes, self_closing) = match rest[..tag_end].strip_suffix('/') {
+                Some(attributes) => (attributes, true),
+                None => (&rest[..tag_end], false),
+            };
+            let attributes = parse_attributes(attributes)?;
+            rest = &rest[tag_end + 1..];
+
+            let value = if self_closing {
+                String::new()
+            } else {
+                let value_end = rest.find("</Item>").ok_or_else(|| bad("Missing </Item>"))?;
+                let value = unescape(&rest[..value_end])?;
+                rest = &rest[value_end + "</Item>".len()..];
+                value
+            };
+
+            let mut item = MetadataItem {
+                name: String::new(),
+                value,
+                sample: None,
+                role: None,
+                domain: None,
+            };
+            for (key, value) in attributes {
+                match key {
+                    "name" => item.name = value,
+                    "sample" => {
+                        item.sample = Some(value.parse().map_err(|_| bad("Bad sample index"))?)
+                    }
+                    "role" => item.role = Some(value),
+                    "domain" => item.domain = Some(value),
+                    _ => {} // Unknown attributes are ignored
+                }
+            }
+            if item.name.is_empty() {
+                return Err(bad("<Item> without a name"));
+            }
+            items.push(item);
+        }
+        Ok(Self { items })
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<GDALMetadata>\n");
+        for item in self.items.iter() {
+            let _ = write!(xml, "  <Item name=\"{}\"", escape(&item.name));
+            if let Some(sample) = item.sample {
+                let _ = write!(xml, " sample=\"{sample}\"");
+            }
+            if let Some(role) = &item.role {
+                let _ = write!(xml, " role=\"{}\"", escape(role));
+            }
+            if let Some(domain) = &item.domain {
+                let _ = write!(xml, " domain=\"{}\"", escape(domain));
+            }
+            let _ = writeln!(xml, ">{}</Item>", escape(&item.value));
+        }
+        xml.push_str("</GDALMetadata>");
+        xml
+    }
+
+    // Dataset level items of the default domain
+    pub fn dataset(&self) -> HashMap<&str, &str> {
+        self.items_of(None)
+    }
+
+    // Band level items of the default domain
+    pub fn band(&self, sample: usize) -> HashMap<&str, &str> {
+        self.items_of(Some(sample))
+    }
+
+    pub fn band_count(&self) -> usize {
+        self.items
+            .iter()
+            .filter_map(|item| item.sample)
+            .max()
+            .map(|max| max + 1)
+            .unwrap_or(0)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.find(None, |item| item.name == name)
+    }
+
+    pub fn get_band(&self, sample: usize, name: &str) -> Option<&str> {
+        self.find(Some(sample), |item| item.name == name)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.set_item(None, name, None, value);
+    }
+
+    pub fn set_band(&mut self, sample: usize, name: &str, value: &str) {
+        self.set_item(Some(sample), name, None, value);
+    }
+
+    pub fn scale(&self, sample: usize) -> Option<f64> {
+        self.role(sample, "scale")?.trim().parse().ok()
+    }
+
+    pub fn offset(&self, sample: usize) -> Option<f64> {
+        self.role(sample, "offset")?.trim().parse().ok()
+    }
+
+    pub fn description(&self, sample: usize) -> Option<&str> {
+        self.role(sample, "description")
+    }
+
+    pub fn unit(&self, sample: usize) -> Option<&str> {
+        self.role(sample, "unittype")
+    }
+
+    pub fn set_scale_offset(&mut self, sample: usize, scale: f64, offset: f64) {
+        self.set_item(Some(sample), "SCALE", Some("scale"), &scale.to_string());
+        self.set_item(Some(sample), "OFFSET", Some("offset"), &offset.to_string());
+    }
+
+    pub fn set_description(&mut self, sample: usize, description: &str) {
+        self.set_item(
+            Some(sample),
+            "DESCRIPTION",
+            Some("description"),
+            description,
+        );
+    }
+
+    pub fn set_unit(&mut self, sample: usize, unit: &str) {
+        self.set_item(Some(sample), "UNITTYPE", Some("unittype"), unit);
+    }
+
+    /// Apply the band's scale and offset to a raw sample value
+    pub fn to_physical(&self, sample: usize, value: f64) -> f64 {
+        value * self.scale(sample).unwrap_or(1.0) + self.offset(sample).unwrap_or(0.0)
+    }
+
+    fn items_of(&self, sample: Option<usize>) -> HashMap<&str, &str> {
+        self.items
+            .iter()
+            .filter(|item| item.sample == sample && item.domain.is_none())
+            .map(|item| (item.name.as_str(), item.value.as_str()))
+            .collect()
+    }
+
+    fn find<F: Fn(&MetadataItem) -> bool>(&self, sample: Option<usize>, f: F) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|item| item.sample == sample && item.domain.is_none() && f(item))
+            .map(|item| item.value.as_str())
+    }
+
+    // Band roles, falling back to GDAL's item names
+    fn role(&self, sample: usize, role: &str) -> Option<&str> {
+        self.find(Some(sample), |item| item.role.as_deref() == Some(role))
+            .or_else(|| self.find(Some(sample), |item| item.name.eq_ignore_ascii_case(role)))
+    }
+
+    fn set_item(&mut self, sample: Option<usize>, name: &str, role: Option<&str>, value: &str) {
+        let existing = self
+            .items
+            .iter_mut()
+            .find(|item| item.sample == sample && item.domain.is_none() && item.name == name);
+        match existing {
+            Some(item) => item.value = value.to_string(),
+            None => self.items.push(MetadataItem {
+                name: name.to_string(),
+                value: value.to_string(),
+                sample,
+                role: role.map(|role| role.to_string()),
+                domain: None,
+            }),
+        }
+    }
+}
+
+fn parse_attributes(text: &str) -> CloudTiffResult<Vec<(&str, String)>> {
+    let mut attributes = vec![];
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let bad = || CloudTiffError::BadMetadata(format!("Bad <Item> attributes: {text}"));
+        let eq = rest.find('=').ok_or_else(bad)?;
+        let key = rest[..eq].trim();
+        rest = rest[eq + 1..].trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let quote = quote.ok_or_else(bad)?;
+        let end = rest[1..].find(quote).ok_or_else(bad)? + 1;
+        attributes.push((key, unescape(&rest[1..end])?));
+        rest = rest[end + 1..].trim_start();
+    }
+    Ok(attributes)
+}
+
+fn unescape(text: &str) -> CloudTiffResult<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| CloudTiffError::BadMetadata(format!("Bad entity in {text}")))?;
+        let c = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        let c = c.ok_or_else(|| CloudTiffError::BadMetadata(format!("Bad entity in {text}")))?;
+        output.push(c);
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
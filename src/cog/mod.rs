@@ -1,31 +1,68 @@
 use crate::geotags::GeoTags;
 use crate::projection::Projection;
-use crate::tiff::Tiff;
-use crate::Region;
+use crate::tiff::{GhostArea, ParseStep, TagId, Tiff, TiffLimits, TiffParser};
+use crate::{ReadRange, Region};
 use std::fmt::Display;
 use std::io::{BufReader, Read, Seek};
+use std::ops::Range;
 
 mod compression;
+mod deferred;
 mod error;
+mod jpeg;
+mod lerc;
 mod level;
+mod metadata;
+mod nodata;
+pub(crate) mod webp;
 
 pub use compression::{Compression, DecompressError, Predictor};
+pub use deferred::{DeferredTiles, DeferredValues};
 pub use error::{CloudTiffError, CloudTiffResult};
+pub use lerc::LercParameters;
 pub use level::Level;
+pub use metadata::{GdalMetadata, MetadataItem};
+pub use nodata::NoData;
 
 #[derive(Clone, Debug)]
 pub struct CloudTiff {
     pub levels: Vec<Level>,
     pub projection: Projection,
+    pub metadata: Option<GdalMetadata>,
+    pub ghost_area: Option<GhostArea>,
 }
 
+// Minimum fetch size, COG headers are usually contiguous so reading ahead saves round trips
+const READ_AHEAD: u64 = 16 * 1024;
+
+// Tile entry arrays at least this big are left in the file by the range reader opens
+//   Their entries are fetched as tiles are read, so opening takes the same time for any size.
+const DEFERRED_TAGS: [TagId; 4] = [
+    TagId::TileOffsets,
+    TagId::TileByteCounts,
+    TagId::StripOffsets,
+    TagId::StripByteCounts,
+];
+const DEFERRED_MIN_BYTES: u64 = READ_AHEAD;
+
+// Largest speculative read of all IFDs, see TiffParser::header_hint
+//   Longer hints are cut short, the rest of the header is read as the parser asks for it
+const MAX_HEADER_FETCH: u64 = 256 * 1024;
+
 impl CloudTiff {
     pub fn open<R: Read + Seek>(source: &mut R) -> CloudTiffResult<Self> {
+        Self::open_with_limits(source, TiffLimits::default())
+    }
+
+    pub fn open_with_limits<R: Read + Seek>(
+        source: &mut R,
+        limits: TiffLimits,
+    ) -> CloudTiffResult<Self> {
         // TODO consider seeking source to start
         let stream = &mut BufReader::new(source);
 
         // TIFF indexing
-        let tiff = Tiff::open(stream)?;
+        let tiff = Tiff::open_with_limits(stream, limits)?;
 
         // Parse GeoTIFF tags
         let ifd0 = tiff.ifd0()?;
@@ -34,15 +71,74 @@ impl CloudTiff {
         Self::from_tiff_and_geo(tiff, geo_tags)
     }
 
+    /// Open with lazily loaded tile entries, see Level::load_tile_entries
+    pub fn open_from_range_reader<R: ReadRange>(source: &R) -> CloudTiffResult<Self> {
+        Self::open_from_range_reader_with_limits(source, TiffLimits::default())
+    }
+
+    pub fn open_from_range_reader_with_limits<R: ReadRange>(
+        source: &R,
+        limits: TiffLimits,
+    ) -> CloudTiffResult<Self> {
+        let mut parser = TiffParser::new()
+            .with_deferred_tags(&DEFERRED_TAGS, DEFERRED_MIN_BYTES)
+            .with_limits(limits);
+        let mut hinted = false;
+        let tiff = loop {
+            match parser.parse()? {
+                ParseStep::NeedBytes(ranges) => {
+                    for (fetch, needed) in coalesce(ranges, header_hint(&parser, &mut hinted)) {
+                        let mut bytes = vec![0; (fetch.end - fetch.start) as usize];
+                        let bytes = match source.read_range(fetch.start, &mut bytes) {
+                            Ok(n) if fetch.start + n as u64 >= needed => {
+                                bytes.truncate(n);
+                                bytes
+                            }
+                            // Read ahead went past the end of the source
+                            _ => source.read_range_to_vec(fetch.start, needed)?,
+                        };
+                        parser.provide(fetch.start, &bytes);
+                    }
+                }
+                ParseStep::Done(tiff) => break tiff,
+            }
+        };
+        let geo_tags = GeoTags::parse(tiff.ifd0()?)?;
+        Self::from_tiff_and_geo(tiff, geo_tags)
+    }
+
     pub fn from_tiff_and_geo(tiff: Tiff, geo: GeoTags) -> CloudTiffResult<Self> {
+        // GDAL metadata, malformed XML is ignored rather than failing the whole COG
+        let metadata = tiff
+            .ifd0()?
+            .get_tag(TagId::GDALMetadata)
+            .ok()
+            .and_then(|tag| GdalMetadata::parse(&tag.as_string_lossy()).ok());
+
         // Map IFDs into COG Levels
         //   Note this skips over any ifds which aren't valid COG levels
         //   TODO check that all levels have the same shape
-        let mut levels: Vec<Level> = tiff
+        let (masks, mut levels): (Vec<Level>, Vec<Level>) = tiff
             .ifds
             .iter()
             .filter_map(|ifd| Level::from_ifd(ifd, tiff.endian).ok())
-            .collect();
+            .map(|level| Level {
+                limits: tiff.limits,
+                ..level
+            })
+            .partition(|level| level.is_mask());
+
+        // Attach internal masks to the image level they cover
+        for mask in masks {
+            let matching = levels.iter_mut().find(|level| {
+                level.mask.is_none()
+                    && level.dimensions == mask.dimensions
+                    && (level.tile_width, level.tile_height) == (mask.tile_width, mask.tile_height)
+            });
+            if let Some(level) = matching {
+                level.mask = Some(Box::new(mask));
+            }
+        }
 
         // Validate levels
         //   COGs should already have levels sorted big to small
@@ -54,10 +150,21 @@ impl CloudTiff {
             return Err(CloudTiffError::NoLevels);
         }
 
+        // Overviews inherit the full resolution nodata value if they don't declare one
+        let nodata = levels[0].nodata;
+        for level in levels.iter_mut() {
+            level.nodata = level.nodata.or(nodata);
+        }
+
         // Projection georeferences any level
         let projection = Projection::from_geo_tags(&geo, levels[0].dimensions)?;
 
-        Ok(Self { levels, projection })
+        Ok(Self {
+            levels,
+            projection,
+            metadata,
+            ghost_area: tiff.ghost_area,
+        })
     }
 
     pub fn bounds_lat_lon_deg(&self) -> CloudTiffResult<Region<f64>> {
@@ -72,6 +179,18 @@ impl CloudTiff {
         self.levels[0].megapixels()
     }
 
+    pub fn nodata(&self) -> Option<NoData> {
+        self.levels[0].nodata
+    }
+
+    // Apply a band's GDAL scale and offset to a raw sample value
+    pub fn to_physical(&self, band: usize, value: f64) -> f64 {
+        match &self.metadata {
+            Some(metadata) => metadata.to_physical(band, value),
+            None => value,
+        }
+    }
+
     pub fn aspect_ratio(&self) -> f64 {
         let (w, h) = self.full_dimensions();
         w as f64 / h as f64
@@ -148,58 +267,132 @@ pub fn disect<R: Read + Seek>(stream: &mut R) -> Result<(), CloudTiffError> {
 mod not_sync {
     use {
         super::*,
+        crate::tiff::TiffError,
         crate::AsyncReadRange,
-        std::io::{Cursor, ErrorKind},
+        futures::future::try_join_all,
+        std::io::{Error, ErrorKind},
         tokio::io::{AsyncRead, AsyncReadExt},
     };
+
     impl CloudTiff {
         pub async fn open_from_async_range_reader<R: AsyncReadRange>(
             source: &R,
         ) -> CloudTiffResult<Self> {
-            let fetch_size = 4096;
-            let mut result = Err(CloudTiffError::TODO);
-            let mut buffer = Vec::with_capacity(fetch_size);
-            for _i in 0..10 {
-                let mut bytes = vec![0; fetch_size];
-                let start = buffer.len();
-                // let end = start + bytes.len();
-                let n = source.read_range_async(start as u64, &mut bytes).await?;
-                buffer.extend_from_slice(&bytes[..n]);
-
-                let mut cursor = Cursor::new(&buffer);
-                result = Self::open(&mut cursor);
-                if let Err(CloudTiffError::ReadError(e)) = &result {
-                    if matches!(e.kind(), ErrorKind::UnexpectedEof) {
-                        continue;
+            Self::open_from_async_range_reader_with_limits(source, TiffLimits::default()).await
+        }
+
+        pub async fn open_from_async_range_reader_with_limits<R: AsyncReadRange>(
+            source: &R,
+            limits: TiffLimits,
+        ) -> CloudTiffResult<Self> {
+            let mut parser = TiffParser::new()
+                .with_deferred_tags(&DEFERRED_TAGS, DEFERRED_MIN_BYTES)
+                .with_limits(limits);
+            let mut hinted = false;
+            let tiff = loop {
+                match parser.parse()? {
+                    ParseStep::NeedBytes(ranges) => {
+                        let reads = coalesce(ranges, header_hint(&parser, &mut hinted))
+                            .into_iter()
+                            .map(|(fetch, needed)| async move {
+                                let mut bytes = vec![0; (fetch.end - fetch.start) as usize];
+                                match source.read_range_async(fetch.start, &mut bytes).await {
+                                    Ok(n) if fetch.start + n as u64 >= needed => {
+                                        bytes.truncate(n);
+                                        Ok((fetch.start, bytes))
+                                    }
+                                    // Read ahead went past the end of the source
+                                    _ => source
+                                        .read_range_to_vec_async(fetch.start, needed)
+                                        .await
+                                        .map(|bytes| (fetch.start, bytes)),
+                                }
+                            });
+                        for (start, bytes) in try_join_all(reads).await? {
+                            parser.provide(start, &bytes);
+                        }
                     }
+                    ParseStep::Done(tiff) => break tiff,
                 }
-                break;
-            }
-            result
+            };
+            let geo_tags = GeoTags::parse(tiff.ifd0()?)?;
+            Self::from_tiff_and_geo(tiff, geo_tags)
         }
 
         pub async fn open_async<R: AsyncRead + Unpin>(source: &mut R) -> CloudTiffResult<Self> {
-            let fetch_size = 4096;
-            let mut result = Err(CloudTiffError::TODO);
-            let mut buffer = Vec::with_capacity(fetch_size);
-            for _i in 0..10 {
-                let mut bytes = vec![0; fetch_size];
-                let n = source.read(&mut bytes).await?;
-                if n == 0 {
-                    break;
-                }
-                buffer.extend_from_slice(&bytes[..n]);
+            Self::open_async_with_limits(source, TiffLimits::default()).await
+        }
 
-                let mut cursor = Cursor::new(&buffer);
-                result = Self::open(&mut cursor);
-                if let Err(CloudTiffError::ReadError(e)) = &result {
-                    if matches!(e.kind(), ErrorKind::UnexpectedEof) {
-                        continue;
+        pub async fn open_async_with_limits<R: AsyncRead + Unpin>(
+            source: &mut R,
+            limits: TiffLimits,
+        ) -> CloudTiffResult<Self> {
+            // Sequential source, everything up to the furthest requested byte is read
+            //   The parser keeps all of it, so reads stop at the header limit
+            let mut parser = TiffParser::new().with_limits(limits);
+            let mut position = 0;
+            let tiff = loop {
+                match parser.parse()? {
+                    ParseStep::NeedBytes(ranges) => {
+                        let end = ranges.iter().map(|range| range.end).max().unwrap_or(0);
+                        if end > limits.max_header_bytes {
+                            return Err(TiffError::HeaderTooLarge(limits.max_header_bytes).into());
+                        }
+                        while position < end {
+                            let len = READ_AHEAD.min(limits.max_header_bytes - position);
+                            let mut bytes = vec![0; len as usize];
+                            let n = source.read(&mut bytes).await?;
+                            if n == 0 {
+                                return Err(Error::new(
+                                    ErrorKind::UnexpectedEof,
+                                    format!("TIFF header needs bytes up to {end}, read {position}"),
+                                )
+                                .into());
+                            }
+                            parser.provide(position, &bytes[..n]);
+                            position += n as u64;
+                        }
                     }
+                    ParseStep::Done(tiff) => break tiff,
                 }
-                break;
+            };
+            let geo_tags = GeoTags::parse(tiff.ifd0()?)?;
+            Self::from_tiff_and_geo(tiff, geo_tags)
+        }
+    }
+}
+
+// Group needed ranges into fetches of at least READ_AHEAD bytes
+//   Returns each fetch with the end of the bytes it must include, speculative bytes are optional
+fn coalesce(ranges: Vec<Range<u64>>, speculative: Option<Range<u64>>) -> Vec<(Range<u64>, u64)> {
+    let mut ranges: Vec<(Range<u64>, u64)> = ranges
+        .into_iter()
+        .map(|range| (range.clone(), range.end))
+        .chain(speculative.map(|range| (range.clone(), range.start)))
+        .collect();
+    ranges.sort_by_key(|(range, _)| range.start);
+    let mut fetches: Vec<(Range<u64>, u64)> = vec![];
+    for (range, range_needed) in ranges {
+        match fetches.last_mut() {
+            Some((fetch, needed)) if range.start <= fetch.end => {
+                *needed = (*needed).max(range_needed);
+                fetch.end = fetch.end.max(range.end);
+            }
+            _ => {
+                let end = range.end.max(range.start.saturating_add(READ_AHEAD));
+                fetches.push((range.start..end, range_needed));
             }
-            result
         }
     }
+    fetches
+}
+
+// GDAL COGs with their IFDs before the tile data have them all read in one request
+fn header_hint(parser: &TiffParser, hinted: &mut bool) -> Option<Range<u64>> {
+    if *hinted {
+        return None;
+    }
+    let hint = parser.header_hint()?;
+    *hinted = true;
+    Some(hint.start..hint.end.min(hint.start.saturating_add(MAX_HEADER_FETCH)))
 }
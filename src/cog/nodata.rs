@@ -0,0 +1,111 @@
+// GDAL_NODATA (Tag 42113)
+// https://gdal.org/en/latest/drivers/raster/gtiff.html#nodata-value
+//   ASCII encoded value that applies to every band, typed by the level's sample format.
+//   A pixel is nodata when all of its samples equal the value.
+
+use crate::raster::SampleFormat;
+use half::f16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoData {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+}
+
+impl NoData {
+    pub fn parse(text: &str, sample_format: SampleFormat) -> Option<Self> {
+        let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        match sample_format {
+            SampleFormat::Float => match text.to_ascii_lowercase().as_str() {
+                "nan" => Some(Self::Float(f64::NAN)),
+                "inf" => Some(Self::Float(f64::INFINITY)),
+                "-inf" => Some(Self::Float(f64::NEG_INFINITY)),
+                _ => text.parse().ok().map(Self::Float),
+            },
+            SampleFormat::Signed => text.parse().ok().map(Self::Signed).or_else(|| {
+                integral(text)
+                    .filter(|v| *v >= i64::MIN as f64 && *v < i64::MAX as f64)
+                    .map(|v| Self::Signed(v as i64))
+            }),
+            _ => text.parse().ok().map(Self::Unsigned).or_else(|| {
+                integral(text)
+                    .filter(|v| *v >= 0.0 && *v < u64::MAX as f64)
+                    .map(|v| Self::Unsigned(v as u64))
+            }),
+        }
+    }
+
+    /// Whether a native endian sample equals the nodata value
+    pub fn matches(&self, sample: &[u8]) -> bool {
+        match (self, sample.len()) {
+            (Self::Unsigned(v), 1) => sample[0] as u64 == *v,
+            (Self::Unsigned(v), 2) => u16::from_ne_bytes([sample[0], sample[1]]) as u64 == *v,
+            (Self::Unsigned(v), 4) => u32::from_ne_bytes(array(sample)) as u64 == *v,
+            (Self::Unsigned(v), 8) => u64::from_ne_bytes(array(sample)) == *v,
+            (Self::Signed(v), 1) => sample[0] as i8 as i64 == *v,
+            (Self::Signed(v), 2) => i16::from_ne_bytes([sample[0], sample[1]]) as i64 == *v,
+            (Self::Signed(v), 4) => i32::from_ne_bytes(array(sample)) as i64 == *v,
+            (Self::Signed(v), 8) => i64::from_ne_bytes(array(sample)) == *v,
+            (Self::Float(v), 2) => {
+                let sample = f16::from_ne_bytes(array(sample));
+                sample == f16::from_f64(*v) || (sample.is_nan() && v.is_nan())
+            }
+            (Self::Float(v), 4) => {
+                let sample = f32::from_ne_bytes(array(sample));
+                sample == *v as f32 || (sample.is_nan() && v.is_nan())
+            }
+            (Self::Float(v), 8) => {
+                let sample = f64::from_ne_bytes(array(sample));
+                sample == *v || (sample.is_nan() && v.is_nan())
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a packed integer sample, right aligned in value, equals the nodata value
+    pub fn matches_packed(&self, value: u64, bits: u16) -> bool {
+        if !(1..=64).contains(&bits) {
+            return false;
+        }
+        match self {
+            Self::Unsigned(v) => value == *v,
+            Self::Signed(v) => {
+                let shift = 64 - bits as u32;
+                ((value << shift) as i64 >> shift) == *v
+            }
+            Self::Float(_) => false,
+        }
+    }
+
+    /// Native endian bytes of the nodata value for a sample size, if representable
+    pub fn to_bytes(&self, bits_per_sample: u16) -> Option<Vec<u8>> {
+        Some(match (self, bits_per_sample) {
+            (Self::Unsigned(v), 8) => vec![u8::try_from(*v).ok()?],
+            (Self::Unsigned(v), 16) => u16::try_from(*v).ok()?.to_ne_bytes().to_vec(),
+            (Self::Unsigned(v), 32) => u32::try_from(*v).ok()?.to_ne_bytes().to_vec(),
+            (Self::Unsigned(v), 64) => v.to_ne_bytes().to_vec(),
+            (Self::Signed(v), 8) => i8::try_from(*v).ok()?.to_ne_bytes().to_vec(),
+            (Self::Signed(v), 16) => i16::try_from(*v).ok()?.to_ne_bytes().to_vec(),
+            (Self::Signed(v), 32) => i32::try_from(*v).ok()?.to_ne_bytes().to_vec(),
+            (Self::Signed(v), 64) => v.to_ne_bytes().to_vec(),
+            (Self::Float(v), 16) => f16::from_f64(*v).to_ne_bytes().to_vec(),
+            (Self::Float(v), 32) => (*v as f32).to_ne_bytes().to_vec(),
+            (Self::Float(v), 64) => v.to_ne_bytes().to_vec(),
+            _ => return None,
+        })
+    }
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut array = [0; N];
+    array.copy_from_slice(&bytes[..N]);
+    array
+}
+
+// GDAL writes integers as floats in some versions, e.g. "0.0"
+fn integral(text: &str) -> Option<f64> {
+    text.parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite() && v.fract() == 0.0)
+}
@@ -0,0 +1,78 @@
+// WebP (Compression 34927)
+// https://gdal.org/en/latest/drivers/raster/cog.html
+//   GDAL writes 8 bit RGB or RGBA tiles, either lossy or lossless.
+//   Decoding is pure rust, encoding requires the "webp" feature (libwebp).
+
+use super::compression::{Compression, DecompressError};
+#[cfg(feature = "webp")]
+use crate::raster::SampleFormat;
+use image_webp::WebPDecoder;
+use std::io::Cursor;
+
+pub fn decode(
+    bytes: &[u8],
+    samples: usize,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, (u32, u32)), DecompressError> {
+    let mut decoder =
+        WebPDecoder::new(Cursor::new(bytes)).map_err(DecompressError::WebPDecodeError)?;
+    let dimensions = decoder.dimensions();
+    let decoded_samples = if decoder.has_alpha() { 4 } else { 3 };
+    if dimensions.0 as usize * dimensions.1 as usize * decoded_samples > max_bytes {
+        return Err(DecompressError::OutputLimit(max_bytes));
+    }
+    let mut buffer = vec![0; dimensions.0 as usize * dimensions.1 as usize * decoded_samples];
+    decoder
+        .read_image(&mut buffer)
+        .map_err(DecompressError::WebPDecodeError)?;
+
+    // Alpha presence is decided per tile, match the level's samples per pixel
+    let buffer = match (decoded_samples, samples) {
+        (3, 3) | (4, 4) => buffer,
+        (3, 4) => buffer
+            .chunks_exact(3)
+            .flat_map(|c| [c[0], c[1], c[2], 255])
+            .collect(),
+        (4, 3) => buffer
+            .chunks_exact(4)
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect(),
+        _ => return Err(DecompressError::CompressionNotSupported(Compression::WebP)),
+    };
+
+    Ok((buffer, dimensions))
+}
+
+// libwebp only takes 8 bit unsigned RGB or RGBA
+#[cfg(feature = "webp")]
+pub fn can_encode(bits_per_sample: &[u16], sample_format: &[SampleFormat]) -> bool {
+    matches!(bits_per_sample.len(), 3 | 4)
+        && bits_per_sample.iter().all(|bits| *bits == 8)
+        && sample_format
+            .iter()
+            .all(|format| *format == SampleFormat::Unsigned)
+}
+
+// Quality is 1-100, where 100 is lossless (as in GDAL)
+#[cfg(feature = "webp")]
+pub fn encode(
+    bytes: &[u8],
+    dimensions: (u32, u32),
+    samples: usize,
+    quality: u8,
+) -> Result<Vec<u8>, DecompressError> {
+    let (width, height) = dimensions;
+    if bytes.len() != width as usize * height as usize * samples {
+        return Err(DecompressError::CompressionNotSupported(Compression::WebP));
+    }
+    let encoder = match samples {
+        3 => webp::Encoder::from_rgb(bytes, width, height),
+        4 => webp::Encoder::from_rgba(bytes, width, height),
+        _ => return Err(DecompressError::CompressionNotSupported(Compression::WebP)),
+    };
+    let quality = quality.clamp(1, 100);
+    encoder
+        .encode_simple(quality == 100, quality as f32)
+        .map(|memory| memory.to_vec())
+        .map_err(DecompressError::WebPEncodeError)
+}
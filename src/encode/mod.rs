@@ -1,9 +1,11 @@
-use crate::cog::{Compression, Predictor};
+use crate::cog::{Compression, GdalMetadata, Predictor};
 use crate::geotags::{GeoKeyId, GeoKeyValue, GeoTags};
-use crate::raster::{PlanarConfiguration, Raster, ResizeFilter};
-use crate::tiff::{Endian, TagData, TagId, Tiff, TiffVariant};
+use crate::raster::{
+    swap_bits, PhotometricInterpretation, PlanarConfiguration, Raster, ResizeFilter,
+};
+use crate::render::GeoRaster;
+use crate::tiff::{Endian, GhostArea, TagData, TagId, Tiff, TiffVariant};
 use crate::Region;
-use image::DynamicImage;
 use std::io::{Seek, SeekFrom, Write};
 
 pub mod error;
@@ -15,6 +17,9 @@ pub enum SupportedCompression {
     Lzw,
     Deflate,
     Uncompressed,
+    Zstd(i32), // Level 1-22, GDAL defaults to 9
+    #[cfg(feature = "webp")]
+    WebP(u8), // Quality 1-100, 100 is lossless
 }
 
 #[derive(Debug)]
@@ -24,25 +29,40 @@ pub struct Encoder {
     endian: Endian,
     variant: TiffVariant,
     compression: SupportedCompression,
+    predictor: Predictor,
     tile_dimensions: (u16, u16),
     filter: ResizeFilter,
+    metadata: Option<GdalMetadata>,
     // TODO tiff tags
 }
 
 impl Encoder {
     #[cfg(feature = "image")]
-    pub fn from_image(img: &DynamicImage) -> EncodeResult<Self> {
+    pub fn from_image(img: &image::DynamicImage) -> EncodeResult<Self> {
+        Self::from_raster(Raster::from_image(img)?)
+    }
+
+    pub fn from_raster(raster: Raster) -> EncodeResult<Self> {
         Ok(Self {
-            raster: Raster::from_image(img)?,
+            raster,
             projection: None,
             endian: Endian::Little,
             variant: TiffVariant::Big,
             compression: SupportedCompression::Lzw,
+            predictor: Predictor::No,
             tile_dimensions: (512, 512),
             filter: ResizeFilter::Nearest,
+            metadata: None,
         })
     }
 
+    // Georeferenced from the raster's projection
+    pub fn from_geo_raster(geo_raster: GeoRaster) -> EncodeResult<Self> {
+        let region = geo_raster.region();
+        let epsg = geo_raster.epsg();
+        Ok(Self::from_raster(geo_raster.raster)?.with_projection(epsg, region))
+    }
+
     pub fn with_projection(mut self, epsg: u16, region: Region<f64>) -> Self {
         self.projection = Some((epsg, region));
         self
@@ -63,6 +83,12 @@ impl Encoder {
         self
     }
 
+    // Horizontal or floating point differencing, ignored by lossy codecs
+    pub fn with_predictor(mut self, predictor: Predictor) -> Self {
+        self.predictor = predictor;
+        self
+    }
+
     pub fn with_big_tiff(mut self, big: bool) -> Self {
         self.variant = if big {
             TiffVariant::Big
@@ -77,13 +103,25 @@ impl Encoder {
         self
     }
 
+    pub fn with_metadata(mut self, metadata: GdalMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
     pub fn encode<W: Write + Seek>(&self, writer: &mut W) -> EncodeResult<()> {
         let endian = self.endian;
         let full_dims = self.raster.dimensions;
         let bps = self.raster.bits_per_sample.clone();
         let interpretation = self.raster.interpretation;
         let planar = PlanarConfiguration::Chunky;
-        let predictor = Predictor::No;
+        let predictor = match self.compression {
+            #[cfg(feature = "webp")]
+            SupportedCompression::WebP(_) => Predictor::No,
+            _ if !self.raster.is_byte_aligned() => Predictor::No, // Packed samples
+            _ => self.predictor,
+        };
+        // Checked before anything is written
+        predictor.check(&bps, &self.raster.sample_format)?;
         let sample_format: Vec<u16> = self
             .raster
             .sample_format
@@ -96,10 +134,21 @@ impl Encoder {
             .iter()
             .map(|v| (*v).into())
             .collect();
-        let compression = match self.compression {
-            SupportedCompression::Lzw => Compression::Lzw,
-            SupportedCompression::Deflate => Compression::DeflateAdobe,
-            SupportedCompression::Uncompressed => Compression::Uncompressed,
+        let (compression, compression_level) = match self.compression {
+            SupportedCompression::Lzw => (Compression::Lzw, None),
+            SupportedCompression::Deflate => (Compression::DeflateAdobe, None),
+            SupportedCompression::Uncompressed => (Compression::Uncompressed, None),
+            SupportedCompression::Zstd(level) => (Compression::Zstd, Some(level)),
+            #[cfg(feature = "webp")]
+            SupportedCompression::WebP(_) => {
+                // Checked before anything is written
+                if !crate::cog::webp::can_encode(&bps, &self.raster.sample_format) {
+                    return Err(EncodeError::CompressionError(
+                        crate::cog::DecompressError::CompressionNotSupported(Compression::WebP),
+                    ));
+                }
+                (Compression::WebP, None)
+            }
         };
 
         // TODO is this necessary?
@@ -117,6 +166,7 @@ impl Encoder {
         };
 
         let mut tiff = Tiff::new(endian, self.variant);
+        tiff.ghost_area = Some(GhostArea::cog()); // IFDs are written before the tiles
 
         // GeoTIFF Tags
         let ifd0 = tiff.ifds.first_mut().unwrap(); // Safe because Tiff::new creates ifd0.
@@ -192,6 +242,11 @@ impl Encoder {
         geo.add_to_ifd(ifd0, endian);
 
         // TODO add any general TIFF tags to idf0
+        if let Some(metadata) = &self.metadata {
+            let mut xml = metadata.to_xml().into_bytes();
+            xml.push(0); // NUL terminated ASCII
+            ifd0.set_tag(TagId::GDALMetadata, TagData::Ascii(xml), endian);
+        }
 
         // Each pyramid is half the previous size
         let overview_levels = ((full_dims.0 as f32 / self.tile_dimensions.0 as f32)
@@ -260,12 +315,30 @@ impl Encoder {
                 TagData::Long(vec![0; number_of_tiles]),
                 endian,
             );
+            if interpretation == PhotometricInterpretation::YCbCr {
+                // Rasters hold a YCbCr triplet for every pixel
+                ifd.set_tag(TagId::YCbCrSubSampling, TagData::Short(vec![1, 1]), endian);
+                if let Some(reference) = self.raster.reference_black_white {
+                    let rationals = reference
+                        .iter()
+                        .map(|v| ((v * 1000.0).round() as u32, 1000))
+                        .collect();
+                    ifd.set_tag(
+                        TagId::ReferenceBlackWhite,
+                        TagData::Rational(rationals),
+                        endian,
+                    );
+                }
+            }
+            if let Some(color_map) = &self.raster.color_map {
+                ifd.set_tag(TagId::ColorMap, TagData::Short(color_map.clone()), endian);
+            }
             ifd.set_tag(
                 TagId::SampleFormat,
                 TagData::Short(sample_format.clone()),
                 endian,
             );
-            if extra_samples.is_empty() {
+            if !extra_samples.is_empty() {
                 ifd.set_tag(
                     TagId::ExtraSamples,
                     TagData::Short(extra_samples.clone()),
@@ -273,7 +346,7 @@ impl Encoder {
                 );
             }
 
-            ifd.0.sort_by(|a, b| a.code.cmp(&b.code)); // TIFF Tags should be sorted
+            ifd.0.sort_by_key(|a| a.code); // TIFF Tags should be sorted
         }
 
         // Encode TIFF
@@ -284,6 +357,12 @@ impl Encoder {
         let mut ifd_tile_bytes = vec![vec![]; overview_levels + 1];
         let (tile_width, tile_height) = self.tile_dimensions;
         let mut prev_overview: Option<Raster> = None;
+        let filter = match (self.filter, &self.raster.color_map) {
+            (_, Some(_)) => ResizeFilter::Nearest, // Palette indices can't be blended
+            (ResizeFilter::Maximum, None) => ResizeFilter::Maximum,
+            (_, None) if !self.raster.is_byte_aligned() => ResizeFilter::Nearest,
+            (filter, None) => filter,
+        };
         for i in 0..=overview_levels {
             let mut tile_offsets = vec![];
             let mut tile_byte_counts = vec![];
@@ -292,8 +371,8 @@ impl Encoder {
             let tile_cols = (width as f32 / tile_width as f32).ceil() as u32;
             let tile_rows = (height as f32 / tile_height as f32).ceil() as u32;
             let img = match prev_overview {
-                Some(raster) => raster.resize(width, height, self.filter)?,
-                None => self.raster.resize(width, height, self.filter)?,
+                Some(raster) => raster.resize(width, height, filter)?,
+                None => self.raster.resize(width, height, filter)?,
             };
             for row in 0..tile_rows {
                 for col in 0..tile_cols {
@@ -305,8 +384,30 @@ impl Encoder {
                         (row + 1) * tile_height as u32,
                     );
                     let tile_raster = img.get_region(region)?;
-                    // TODO endian
-                    let tile_bytes = compression.encode(&tile_raster.buffer[..])?;
+                    let tile_bytes = match self.compression {
+                        #[cfg(feature = "webp")]
+                        SupportedCompression::WebP(quality) => crate::cog::webp::encode(
+                            &tile_raster.buffer,
+                            tile_raster.dimensions,
+                            bps.len(),
+                            quality,
+                        )?,
+                        _ => {
+                            let mut buffer = tile_raster.buffer;
+                            endian.swap_samples(
+                                &mut buffer,
+                                &swap_bits(&bps, &tile_raster.sample_format),
+                            );
+                            predictor.encode(
+                                &mut buffer,
+                                tile_width as usize,
+                                bps.first().copied().unwrap_or(0) as usize,
+                                bps.len(),
+                                endian,
+                            )?;
+                            compression.encode_with_level(&buffer, compression_level)?
+                        }
+                    };
                     writer.write_all(&tile_bytes)?;
                     tile_byte_counts.push(tile_bytes.len() as u32);
                 }
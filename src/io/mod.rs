@@ -44,7 +44,8 @@ pub trait ReadRange {
     }
 
     fn read_range_to_vec(&self, start: u64, end: u64) -> Result<Vec<u8>> {
-        let n = (end - start) as usize;
+        let n = usize::try_from(end.saturating_sub(start))
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
         let mut buf = vec![0; n];
         self.read_range_exact(start, &mut buf)?;
         Ok(buf)
@@ -117,8 +118,9 @@ mod not_sync {
         }
 
         fn read_range_to_vec_async(&self, start: u64, end: u64) -> BoxFuture<'_, Result<Vec<u8>>> {
-            let n = (end - start) as usize;
+            let n = usize::try_from(end.saturating_sub(start));
             Box::pin(async move {
+                let n = n.map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
                 let mut buf = vec![0; n];
                 match self.read_range_async(start, &mut buf).await {
                     Ok(bytes_read) if bytes_read == n => Ok(buf),
@@ -13,7 +13,7 @@ pub use proj4rs::Proj;
 pub use projection::primatives::{Point2D, Region, UnitFloat};
 pub use projection::Projection;
 pub use raster::{Raster, ResizeFilter};
-pub use render::tiles;
+pub use render::{tiles, GeoRaster};
 
 // IO exports
 #[cfg(feature = "http")]
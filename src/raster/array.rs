@@ -0,0 +1,118 @@
+// ndarray conversions
+//   PlanarConfiguration::Planar arrays are bands x rows x cols
+//   PlanarConfiguration::Chunky arrays are rows x cols x bands, the layout of Raster::buffer
+
+use super::{
+    f16, PhotometricInterpretation, PlanarConfiguration, Raster, RasterError, Sample, SampleType,
+};
+use ndarray::{Array3, ArrayView3};
+
+// Array of the raster's own sample type
+#[derive(Debug, Clone, PartialEq)]
+pub enum RasterArray {
+    U8(Array3<u8>),
+    U16(Array3<u16>),
+    U32(Array3<u32>),
+    U64(Array3<u64>),
+    I8(Array3<i8>),
+    I16(Array3<i16>),
+    I32(Array3<i32>),
+    I64(Array3<i64>),
+    F16(Array3<f16>),
+    F32(Array3<f32>),
+    F64(Array3<f64>),
+}
+
+impl Raster {
+    /// Samples converted to T with saturation
+    pub fn to_array<T: Sample>(
+        &self,
+        layout: PlanarConfiguration,
+    ) -> Result<Array3<T>, RasterError> {
+        let (width, height) = self.dimensions;
+        let shape = (height as usize, width as usize, self.bits_per_sample.len());
+        let array = Array3::from_shape_vec(shape, self.samples::<T>()?)
+            .map_err(|e| RasterError::NotSupported(format!("{e}")))?;
+        match layout {
+            PlanarConfiguration::Chunky => Ok(array),
+            PlanarConfiguration::Planar => Ok(array
+                .permuted_axes([2, 0, 1])
+                .as_standard_layout()
+                .to_owned()),
+            PlanarConfiguration::Unknown => Err(unknown_layout()),
+        }
+    }
+
+    /// Array of the sample type, 24 bit floats are widened to f32
+    pub fn to_raster_array(&self, layout: PlanarConfiguration) -> Result<RasterArray, RasterError> {
+        let sample_types = self.sample_types()?;
+        let unsupported = || {
+            RasterError::NotSupported(format!(
+                "Array of {:?} with bits per sample {:?}",
+                self.sample_format, self.bits_per_sample
+            ))
+        };
+        let first = *sample_types.first().ok_or_else(unsupported)?;
+        if sample_types.iter().any(|t| *t != first) {
+            return Err(unsupported());
+        }
+        Ok(match first {
+            SampleType::U8 => RasterArray::U8(self.to_array(layout)?),
+            SampleType::U16 => RasterArray::U16(self.to_array(layout)?),
+            SampleType::U32 => RasterArray::U32(self.to_array(layout)?),
+            SampleType::U64 => RasterArray::U64(self.to_array(layout)?),
+            SampleType::I8 => RasterArray::I8(self.to_array(layout)?),
+            SampleType::I16 => RasterArray::I16(self.to_array(layout)?),
+            SampleType::I32 => RasterArray::I32(self.to_array(layout)?),
+            SampleType::I64 => RasterArray::I64(self.to_array(layout)?),
+            SampleType::F16 => RasterArray::F16(self.to_array(layout)?),
+            SampleType::F24 | SampleType::F32 => RasterArray::F32(self.to_array(layout)?),
+            SampleType::F64 => RasterArray::F64(self.to_array(layout)?),
+            _ => return Err(unsupported()),
+        })
+    }
+
+    pub fn from_array<T: Sample>(
+        array: ArrayView3<T>,
+        layout: PlanarConfiguration,
+        interpretation: PhotometricInterpretation,
+    ) -> Result<Self, RasterError> {
+        let array = match layout {
+            PlanarConfiguration::Chunky => array,
+            PlanarConfiguration::Planar => array.permuted_axes([1, 2, 0]),
+            PlanarConfiguration::Unknown => return Err(unknown_layout()),
+        };
+        let (height, width, bands) = array.dim();
+        let samples = array.iter().copied().collect();
+        Self::from_samples(
+            (width as u32, height as u32),
+            samples,
+            bands,
+            interpretation,
+        )
+    }
+
+    pub fn from_raster_array(
+        array: &RasterArray,
+        layout: PlanarConfiguration,
+        interpretation: PhotometricInterpretation,
+    ) -> Result<Self, RasterError> {
+        match array {
+            RasterArray::U8(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::U16(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::U32(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::U64(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::I8(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::I16(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::I32(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::I64(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::F16(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::F32(a) => Self::from_array(a.view(), layout, interpretation),
+            RasterArray::F64(a) => Self::from_array(a.view(), layout, interpretation),
+        }
+    }
+}
+
+fn unknown_layout() -> RasterError {
+    RasterError::NotSupported("Array layout must be Chunky or Planar".into())
+}
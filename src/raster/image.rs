@@ -1,242 +1,542 @@
-#![cfg(feature = "image")]
+// Photometric conversion to 8 bit RGBA, available with or without the image feature
+//   https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf (Section 21, 23)
 
 use super::{
-    photometrics::PhotometricInterpretation as Style, ExtraSamples, RasterError, SampleFormat,
+    photometrics::PhotometricInterpretation as Style, Raster, RasterError, SampleFormat, SampleType,
 };
-use crate::raster::Raster;
-use crate::tiff::Endian;
-use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
+
+// ITU-R BT.601 luma coefficients, the default YCbCrCoefficients
+const LUMA: (f64, f64, f64) = (0.299, 0.587, 0.114);
+
+// CIE D65 white point, the default WhitePoint
+const WHITE: (f64, f64, f64) = (0.95047, 1.0, 1.08883);
 
 impl Raster {
-    pub fn get_pixel_rgba(&self, x: u32, y: u32) -> Option<Rgba<u8>> {
-        let p = self.get_pixel(x, y)?;
-        Some(match self.bits_per_sample.as_slice() {
-            [8] => Rgba([p[0], p[0], p[0], 255]),
-            [8, 8] => Rgba([p[0], p[0], p[0], p[1]]),
-            [8, 8, 8] => Rgba([p[0], p[1], p[2], 255]),
-            [8, 8, 8, 8] => Rgba([p[0], p[1], p[2], p[3]]),
-            [16] => {
-                let v: i16 = self.endian.decode([p[0], p[1]]).ok()?;
-                let v8 = (v / 10).clamp(0, 255) as u8;
-                Rgba([v8, v8, v8, 255])
-            }
-            _ => return None,
-        })
+    /// Pixel converted to 8 bit RGBA according to the photometric interpretation
+    ///   Signed and floating point samples use the fixed SampleType::display_range
+    ///   Pixels outside the mask are transparent
+    pub fn get_pixel_rgba8(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        let pixel = self.get_pixel(x, y)?;
+        let mut rgba = self.pixel_to_rgba8(&pixel, &self.display_ranges())?;
+        if !self.is_valid(x, y) {
+            rgba[3] = 0;
+        }
+        Some(rgba)
     }
-}
 
-impl TryInto<DynamicImage> for Raster {
-    type Error = String;
+    /// Raster converted to an 8 bit RGBA buffer according to the photometric interpretation
+    ///   Signed and floating point samples are stretched between their valid min and max
+    ///   Pixels outside the mask are transparent
+    pub fn to_rgba8(&self) -> Result<Vec<u8>, RasterError> {
+        let unsupported = || {
+            RasterError::NotSupported(format!(
+                "RGBA conversion of {:?} with bits per sample {:?}",
+                self.interpretation, self.bits_per_sample
+            ))
+        };
+        if self.bits_per_pixel == 0 {
+            return Err(unsupported());
+        }
+        let (width, height) = self.dimensions;
+        let ranges = self.stretched_ranges();
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+        if self.is_byte_aligned() {
+            let bytes_per_pixel = (self.bits_per_pixel / 8) as usize;
+            for pixel in self.buffer.chunks_exact(bytes_per_pixel) {
+                rgba.extend_from_slice(
+                    &self
+                        .pixel_to_rgba8(pixel, &ranges)
+                        .ok_or_else(unsupported)?,
+                );
+            }
+        } else {
+            for y in 0..height {
+                for x in 0..width {
+                    let pixel = self.get_pixel(x, y).ok_or_else(unsupported)?;
+                    rgba.extend_from_slice(
+                        &self
+                            .pixel_to_rgba8(&pixel, &ranges)
+                            .ok_or_else(unsupported)?,
+                    );
+                }
+            }
+        }
+        self.mask_alpha(&mut rgba);
+        Ok(rgba)
+    }
 
-    fn try_into(self) -> Result<DynamicImage, Self::Error> {
-        self.into_image()
+    // Zero the alpha of RGBA pixels outside the mask
+    fn mask_alpha<T: Default>(&self, rgba: &mut [T]) {
+        if let Some(mask) = &self.mask {
+            for (pixel, valid) in rgba.chunks_exact_mut(4).zip(mask) {
+                if *valid == 0 {
+                    pixel[3] = T::default();
+                }
+            }
+        }
     }
-}
 
-impl TryInto<RgbaImage> for Raster {
-    type Error = String;
+    // Signed and floating point samples have no natural black and white, the color samples are
+    //   stretched between the smallest and largest finite values of the valid pixels
+    fn stretched_ranges(&self) -> Vec<(f64, f64)> {
+        let mut ranges = self.display_ranges();
+        let colors = ranges.len().saturating_sub(self.extra_samples.len());
+        let stretch: Vec<usize> = (0..colors)
+            .filter(|i| {
+                matches!(
+                    self.sample_format.get(*i),
+                    Some(
+                        SampleFormat::Signed
+                            | SampleFormat::Float
+                            | SampleFormat::ComplexInt
+                            | SampleFormat::ComplexFloat
+                    )
+                )
+            })
+            .collect();
+        if stretch.is_empty() || self.interpretation == Style::RGBPalette {
+            return ranges;
+        }
+
+        let mut bounds = vec![(f64::INFINITY, f64::NEG_INFINITY); colors];
+        let (width, height) = self.dimensions;
+        for y in 0..height {
+            for x in 0..width {
+                if !self.is_valid(x, y) {
+                    continue;
+                }
+                let Some(samples) = self.get_pixel(x, y).and_then(|p| self.pixel_samples(&p))
+                else {
+                    continue;
+                };
+                for i in &stretch {
+                    let (min, max) = &mut bounds[*i];
+                    if let Some(v) = samples.get(*i).filter(|v| v.is_finite()) {
+                        *min = min.min(*v);
+                        *max = max.max(*v);
+                    }
+                }
+            }
+        }
+        for i in stretch {
+            let (min, max) = bounds[i];
+            if min < max {
+                ranges[i] = (min, max);
+            }
+        }
+        ranges
+    }
 
-    fn try_into(self) -> Result<RgbaImage, Self::Error> {
-        self.into_rgba()
+    fn pixel_to_rgba8(&self, pixel: &[u8], ranges: &[(f64, f64)]) -> Option<[u8; 4]> {
+        let [r, g, b, a] = self.pixel_to_rgba(pixel, ranges)?;
+        let to_u8 = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Some([to_u8(r), to_u8(g), to_u8(b), to_u8(a)])
     }
-}
 
-impl Raster {
-    pub fn into_rgba(self) -> Result<RgbaImage, String> {
-        let Raster {
-            dimensions: (width, height),
-            buffer,
-            bits_per_sample,
-            interpretation: _,
-            endian,
-            ..
-        } = self;
-
-        match bits_per_sample.as_slice() {
-            [8] => {
-                let buf8: Vec<u8> = buffer.into_iter().flat_map(|v| [v, v, v, 255]).collect();
-                RgbaImage::from_raw(width, height, buf8)
+    // Display RGBA, nominally 0 to 1 but not clamped so float samples keep their range
+    //   Sample values are scaled from the (black, white) ranges of each sample
+    fn pixel_to_rgba(&self, pixel: &[u8], ranges: &[(f64, f64)]) -> Option<[f64; 4]> {
+        let samples = self.pixel_samples(pixel)?;
+        let norm = |i: usize| {
+            let (black, white) = ranges.get(i)?;
+            Some((samples.get(i)? - black) / (white - black))
+        };
+
+        // Color channels, followed by an optional alpha sample
+        let (rgb, channels) = match self.interpretation {
+            Style::WhiteIsZero => {
+                let v = 1.0 - norm(0)?;
+                ((v, v, v), 1)
+            }
+            Style::RGB => ((norm(0)?, norm(1)?, norm(2)?), 3),
+            Style::RGBPalette => {
+                let [r, g, b] = self.palette_color(samples[0] as usize)?;
+                let max = u16::MAX as f64;
+                ((r as f64 / max, g as f64 / max, b as f64 / max), 1)
             }
-            [8, 8] => None,
-            [16] => endian.decode_all(&buffer).and_then(|buffer: Vec<u16>| {
-                let buf8: Vec<u8> = buffer
-                    .into_iter()
-                    .map(|v16| (v16 >> 8) as u8)
-                    .flat_map(|v8| [v8, v8, v8, 255])
-                    .collect();
-                RgbaImage::from_raw(width, height, buf8)
-            }),
-            [16, 16] => None,
-            [8, 8, 8] => {
-                let buf8: Vec<u8> = buffer
-                    .chunks_exact(3)
-                    .flat_map(|c| [c[0], c[1], c[2], 255])
-                    .collect();
-                RgbaImage::from_raw(width, height, buf8)
+            Style::CMYK => {
+                let k = 1.0 - norm(3)?;
+                let rgb = (
+                    (1.0 - norm(0)?) * k,
+                    (1.0 - norm(1)?) * k,
+                    (1.0 - norm(2)?) * k,
+                );
+                (rgb, 4)
             }
-            [8, 8, 8, 8] => RgbaImage::from_raw(width, height, buffer),
-            [16, 16, 16] => endian.decode_all(&buffer).and_then(|buffer: Vec<u16>| {
-                let buf8: Vec<u8> = buffer
-                    .chunks_exact(3)
-                    .flat_map(|c| [(c[0] >> 8) as u8, (c[1] >> 8) as u8, (c[2] >> 8) as u8, 255])
-                    .collect();
-                RgbaImage::from_raw(width, height, buf8)
-            }),
-            [16, 16, 16, 16] => endian.decode_all(&buffer).and_then(|buffer: Vec<u16>| {
-                let buf8: Vec<u8> = buffer.into_iter().map(|v16| (v16 >> 8) as u8).collect();
-                RgbaImage::from_raw(width, height, buf8)
-            }),
-            [32, 32, 32] => endian.decode_all(&buffer).and_then(|buffer: Vec<u32>| {
-                let buf8: Vec<u8> = buffer
-                    .chunks_exact(3)
-                    .flat_map(|c| {
-                        [
-                            (c[0] >> 24) as u8,
-                            (c[1] >> 24) as u8,
-                            (c[2] >> 24) as u8,
-                            255,
-                        ]
+            Style::YCbCr => (self.ycbcr_to_rgb(&samples)?, 3),
+            Style::CIELab | Style::ICCLab => (self.lab_to_rgb(&samples)?, 3),
+            Style::Unknown if samples.len() >= 3 => ((norm(0)?, norm(1)?, norm(2)?), 3),
+            _ => {
+                let v = norm(0)?;
+                ((v, v, v), 1)
+            }
+        };
+        let alpha = match samples.len() > channels {
+            true => norm(channels)?,
+            false => 1.0,
+        };
+        Some([rgb.0, rgb.1, rgb.2, alpha])
+    }
+
+    // Sample values of a pixel from get_pixel, the magnitude of complex samples
+    pub(super) fn pixel_samples(&self, pixel: &[u8]) -> Option<Vec<f64>> {
+        // Packed samples are most significant first
+        if !self.is_byte_aligned() {
+            let value = pixel.iter().fold(0u64, |v, b| (v << 8) | *b as u64);
+            let mut shift = self.bits_per_pixel;
+            return Some(
+                self.bits_per_sample
+                    .iter()
+                    .enumerate()
+                    .map(|(i, bits)| {
+                        shift -= *bits as u32;
+                        let v = (value >> shift) & ((1 << bits) - 1);
+                        match self.sample_format.get(i) {
+                            Some(SampleFormat::Signed) if v >> (bits - 1) != 0 => {
+                                v as f64 - 2f64.powi(*bits as i32)
+                            }
+                            _ => v as f64,
+                        }
                     })
-                    .collect();
-                RgbaImage::from_raw(width, height, buf8)
-            }),
-            [32, 32, 32, 32] => endian.decode_all(&buffer).and_then(|buffer: Vec<u32>| {
-                let buf8: Vec<u8> = buffer.into_iter().map(|v32| (v32 >> 24) as u8).collect();
-                RgbaImage::from_raw(width, height, buf8)
-            }),
-            _ => None,
-        }
-        .ok_or(format!("RGBA Not Supported for BPS={bits_per_sample:?}"))
+                    .collect(),
+            );
+        }
+        let mut offset = 0;
+        let mut samples = Vec::with_capacity(self.bits_per_sample.len());
+        for sample_type in self.sample_types().ok()? {
+            samples.push(sample_type.to_f64(pixel.get(offset..)?)?);
+            offset += sample_type.bytes();
+        }
+        Some(samples)
     }
 
-    pub fn into_image(self) -> Result<DynamicImage, String> {
-        let Raster {
-            dimensions: (width, height),
-            buffer,
-            bits_per_sample,
-            interpretation: _,
-            endian,
-            ..
-        } = self;
-
-        match bits_per_sample.as_slice() {
-            [8] => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma8),
-            [8, 8] => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLumaA8),
-            [16] => endian.decode_all(&buffer).and_then(|buffer| {
-                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma16)
-            }),
-            [16, 16] => endian.decode_all(&buffer).and_then(|buffer| {
-                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLumaA16)
-            }),
-            [8, 8, 8] => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb8),
-            [8, 8, 8, 8] => {
-                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba8)
-            }
-            [16, 16, 16] => endian.decode_all(&buffer).and_then(|buffer| {
-                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb16)
-            }),
-            [16, 16, 16, 16] => endian.decode_all(&buffer).and_then(|buffer| {
-                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba16)
-            }),
-            [32, 32, 32] => endian.decode_all(&buffer).and_then(|buffer| {
-                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb32F)
-            }),
-            [32, 32, 32, 32] => endian.decode_all(&buffer).and_then(|buffer| {
-                ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba32F)
-            }),
-            _ => None,
-        }
-        .ok_or(format!(
-            "Bits Per Sample Not Supported: {bits_per_sample:?}"
-        ))
+    fn display_ranges(&self) -> Vec<(f64, f64)> {
+        (0..self.bits_per_sample.len())
+            .map(|i| self.display_range(i))
+            .collect()
     }
 
-    pub fn from_image(img: &DynamicImage) -> Result<Self, RasterError> {
-        let dimensions = (img.width(), img.height());
-        let buffer = img.as_bytes().to_vec();
-        let endian = if cfg!(target_endian = "big") {
-            Endian::Big
-        } else {
-            Endian::Little
+    // Values shown as black and white, see SampleType::display_range
+    fn display_range(&self, sample: usize) -> (f64, f64) {
+        let bits = self.bits_per_sample[sample];
+        let format = self
+            .sample_format
+            .get(sample)
+            .copied()
+            .unwrap_or(SampleFormat::Unsigned);
+        match SampleType::from_format(format, bits) {
+            Some(sample_type) => sample_type.display_range(),
+            None if format == SampleFormat::Signed => (0.0, 2f64.powi(bits as i32 - 1) - 1.0),
+            None => (0.0, 2f64.powi(bits as i32) - 1.0),
+        }
+    }
+
+    fn ycbcr_to_rgb(&self, samples: &[f64]) -> Option<(f64, f64, f64)> {
+        let [y, cb, cr] = samples.get(..3)? else {
+            return None;
         };
+        let bits = self.bits_per_sample[0] as i32;
+        let max = 2f64.powi(bits) - 1.0;
+        let half = 2f64.powi(bits - 1);
+        let [y0, y1, cb0, cb1, cr0, cr1] = self
+            .reference_black_white
+            .unwrap_or([0.0, max, half, max, half, max]);
 
-        let (interpretation, bits_per_sample, sample_format, extra_samples) = match img {
-            DynamicImage::ImageLuma16(_) => (
-                Style::BlackIsZero,
-                vec![16],
-                vec![SampleFormat::Unsigned],
-                vec![],
-            ),
-            DynamicImage::ImageLuma8(_) => (
-                Style::BlackIsZero,
-                vec![8],
-                vec![SampleFormat::Unsigned],
-                vec![],
-            ),
-            DynamicImage::ImageLumaA8(_) => (
-                Style::BlackIsZero,
-                vec![8, 8],
-                vec![SampleFormat::Unsigned; 2],
-                vec![ExtraSamples::AssociatedAlpha],
-            ),
-            DynamicImage::ImageRgb8(_) => (
-                Style::RGB,
-                vec![8, 8, 8],
-                vec![SampleFormat::Unsigned; 3],
-                vec![],
-            ),
-            DynamicImage::ImageRgba8(_) => (
-                Style::RGB,
-                vec![8, 8, 8, 8],
-                vec![SampleFormat::Unsigned; 4],
-                vec![ExtraSamples::AssociatedAlpha],
-            ),
-            DynamicImage::ImageLumaA16(_) => (
-                Style::BlackIsZero,
-                vec![16, 16],
-                vec![SampleFormat::Unsigned; 2],
-                vec![ExtraSamples::AssociatedAlpha],
-            ),
-            DynamicImage::ImageRgb16(_) => (
-                Style::RGB,
-                vec![16, 16, 16],
-                vec![SampleFormat::Unsigned; 3],
-                vec![],
-            ),
-            DynamicImage::ImageRgba16(_) => (
-                Style::RGB,
-                vec![16, 16, 16, 16],
-                vec![SampleFormat::Unsigned; 4],
-                vec![ExtraSamples::AssociatedAlpha],
-            ),
-            DynamicImage::ImageRgb32F(_) => (
-                Style::RGB,
-                vec![32, 32, 32],
-                vec![SampleFormat::Float; 3],
-                vec![],
-            ),
-            DynamicImage::ImageRgba32F(_) => (
-                Style::RGB,
-                vec![32, 32, 32, 32],
-                vec![SampleFormat::Float; 4],
-                vec![ExtraSamples::AssociatedAlpha],
-            ),
-            _ => (
-                Style::Unknown,
-                vec![8],
-                vec![SampleFormat::Unsigned],
-                vec![],
+        // Scale codes to full range luma 0 to 1 and chroma -0.5 to 0.5
+        let y = (y - y0) / (y1 - y0);
+        let cb = (cb - cb0) * 0.5 / (cb1 - cb0);
+        let cr = (cr - cr0) * 0.5 / (cr1 - cr0);
+
+        let (luma_red, luma_green, luma_blue) = LUMA;
+        let r = y + (2.0 - 2.0 * luma_red) * cr;
+        let b = y + (2.0 - 2.0 * luma_blue) * cb;
+        let g = (y - luma_blue * b - luma_red * r) / luma_green;
+        Some((r, g, b))
+    }
+
+    fn lab_to_rgb(&self, samples: &[f64]) -> Option<(f64, f64, f64)> {
+        let [l, a, b] = samples.get(..3)? else {
+            return None;
+        };
+        // CIELab a* and b* are signed, ICCLab a* and b* are offset by 128
+        let icc = self.interpretation == Style::ICCLab;
+        let signed = |v: f64, bits: i32| match v >= 2f64.powi(bits - 1) {
+            true => v - 2f64.powi(bits),
+            false => v,
+        };
+        let (l, a, b) = match (self.bits_per_sample[0], icc) {
+            (8, false) => (l * 100.0 / 255.0, signed(*a, 8), signed(*b, 8)),
+            (8, true) => (l * 100.0 / 255.0, a - 128.0, b - 128.0),
+            (16, false) => (
+                l * 100.0 / 65535.0,
+                signed(*a, 16) / 256.0,
+                signed(*b, 16) / 256.0,
             ),
+            (16, true) => (l * 100.0 / 65280.0, a / 256.0 - 128.0, b / 256.0 - 128.0),
+            _ => return None,
+        };
+
+        // Lab to XYZ
+        let f_inv = |t: f64| match t > 6.0 / 29.0 {
+            true => t.powi(3),
+            false => 3.0 * (6.0f64 / 29.0).powi(2) * (t - 4.0 / 29.0),
+        };
+        let fy = (l + 16.0) / 116.0;
+        let x = WHITE.0 * f_inv(fy + a / 500.0);
+        let y = WHITE.1 * f_inv(fy);
+        let z = WHITE.2 * f_inv(fy - b / 200.0);
+
+        // XYZ to sRGB
+        let gamma = |c: f64| match c <= 0.0031308 {
+            true => 12.92 * c,
+            false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
         };
+        Some((
+            gamma(3.2406 * x - 1.5372 * y - 0.4986 * z),
+            gamma(-0.9689 * x + 1.8758 * y + 0.0415 * z),
+            gamma(0.0557 * x - 0.2040 * y + 1.0570 * z),
+        ))
+    }
+}
+
+#[cfg(feature = "image")]
+mod dynamic_image {
+    use super::*;
+    use crate::raster::ExtraSamples;
+    use crate::tiff::Endian;
+    use image::{DynamicImage, ImageBuffer, Rgba, Rgba32FImage, RgbaImage};
+
+    impl Raster {
+        pub fn get_pixel_rgba(&self, x: u32, y: u32) -> Option<Rgba<u8>> {
+            self.get_pixel_rgba8(x, y).map(Rgba)
+        }
+
+        // Palette pixel, index in the first sample and an optional alpha in the second
+        fn palette_rgba16(&self, pixel: &[u8]) -> Option<[u16; 4]> {
+            let samples = self.pixel_samples(pixel)?;
+            let alpha = match samples.get(1) {
+                Some(alpha) => {
+                    let (black, white) = self.display_range(1);
+                    let alpha = (alpha - black) / (white - black);
+                    (alpha.clamp(0.0, 1.0) * 65535.0).round() as u16
+                }
+                None => u16::MAX,
+            };
+            let [r, g, b] = self.palette_color(*samples.first()? as usize)?;
+            Some([r, g, b, alpha])
+        }
+
+        fn expand_palette(&self) -> Option<Vec<u16>> {
+            let (width, height) = self.dimensions;
+            let mut buffer = Vec::with_capacity(width as usize * height as usize * 4);
+            for y in 0..height {
+                for x in 0..width {
+                    buffer.extend_from_slice(&self.palette_rgba16(&self.get_pixel(x, y)?)?);
+                }
+            }
+            Some(buffer)
+        }
+    }
 
-        Self::new(
-            dimensions,
-            buffer,
-            bits_per_sample,
-            interpretation,
-            sample_format,
-            extra_samples,
-            endian,
-        )
+    impl TryInto<DynamicImage> for Raster {
+        type Error = String;
+
+        fn try_into(self) -> Result<DynamicImage, Self::Error> {
+            self.into_image()
+        }
+    }
+
+    impl TryInto<RgbaImage> for Raster {
+        type Error = String;
+
+        fn try_into(self) -> Result<RgbaImage, Self::Error> {
+            self.into_rgba()
+        }
+    }
+
+    impl Raster {
+        pub fn into_rgba(self) -> Result<RgbaImage, String> {
+            let (width, height) = self.dimensions;
+            let buffer = self.to_rgba8().map_err(|e| format!("{e:?}"))?;
+            RgbaImage::from_raw(width, height, buffer).ok_or(format!(
+                "RGBA Not Supported for BPS={:?}",
+                self.bits_per_sample
+            ))
+        }
+
+        pub fn into_rgba32f(self) -> Result<Rgba32FImage, String> {
+            let (width, height) = self.dimensions;
+            let unsupported = || {
+                format!(
+                    "RGBA Not Supported for BPS={:?} and {:?}",
+                    self.bits_per_sample, self.sample_format
+                )
+            };
+            let ranges = self.stretched_ranges();
+            let mut buffer = Vec::with_capacity(width as usize * height as usize * 4);
+            for y in 0..height {
+                for x in 0..width {
+                    let pixel = self.get_pixel(x, y).ok_or_else(unsupported)?;
+                    let rgba = self
+                        .pixel_to_rgba(&pixel, &ranges)
+                        .ok_or_else(unsupported)?;
+                    buffer.extend(rgba.map(|v| v as f32));
+                }
+            }
+            self.mask_alpha(&mut buffer);
+            Rgba32FImage::from_raw(width, height, buffer).ok_or_else(unsupported)
+        }
+
+        pub fn into_image(self) -> Result<DynamicImage, String> {
+            match self.interpretation {
+                Style::RGBPalette if self.color_map.is_some() => {
+                    let (width, height) = self.dimensions;
+                    return self
+                        .expand_palette()
+                        .and_then(|buffer| ImageBuffer::from_raw(width, height, buffer))
+                        .map(DynamicImage::ImageRgba16)
+                        .ok_or(format!(
+                            "Palette Not Supported for BPS={:?}",
+                            self.bits_per_sample
+                        ));
+                }
+                _ if !self.is_byte_aligned() => {
+                    return self.into_rgba().map(DynamicImage::ImageRgba8)
+                }
+                Style::BlackIsZero | Style::RGB | Style::Unknown => {}
+                // Other color spaces are converted to RGBA
+                _ => return self.into_rgba().map(DynamicImage::ImageRgba8),
+            }
+
+            // Sample types without an image equivalent, and signed or float samples which are
+            //   stretched as in to_rgba8, are converted to float RGBA
+            let sample_types = self.sample_types().map_err(|e| format!("{e:?}"))?;
+            let same = sample_types.windows(2).all(|w| w[0] == w[1]);
+            match (sample_types.first(), sample_types.len()) {
+                (Some(SampleType::U8 | SampleType::U16), 1..=4) if same => {}
+                _ => return self.into_rgba32f().map(DynamicImage::ImageRgba32F),
+            }
+
+            let Raster {
+                dimensions: (width, height),
+                buffer,
+                bits_per_sample,
+                interpretation: _,
+                ..
+            } = self;
+            let endian = Endian::native();
+
+            match bits_per_sample.as_slice() {
+                [8] => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma8),
+                [8, 8] => {
+                    ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLumaA8)
+                }
+                [16] => endian.decode_all(&buffer).and_then(|buffer| {
+                    ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma16)
+                }),
+                [16, 16] => endian.decode_all(&buffer).and_then(|buffer| {
+                    ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLumaA16)
+                }),
+                [8, 8, 8] => {
+                    ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb8)
+                }
+                [8, 8, 8, 8] => {
+                    ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba8)
+                }
+                [16, 16, 16] => endian.decode_all(&buffer).and_then(|buffer| {
+                    ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb16)
+                }),
+                [16, 16, 16, 16] => endian.decode_all(&buffer).and_then(|buffer| {
+                    ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba16)
+                }),
+                _ => None,
+            }
+            .ok_or(format!(
+                "Bits Per Sample Not Supported: {bits_per_sample:?}"
+            ))
+        }
+
+        pub fn from_image(img: &DynamicImage) -> Result<Self, RasterError> {
+            let dimensions = (img.width(), img.height());
+            let buffer = img.as_bytes().to_vec();
+
+            let (interpretation, bits_per_sample, sample_format, extra_samples) = match img {
+                DynamicImage::ImageLuma16(_) => (
+                    Style::BlackIsZero,
+                    vec![16],
+                    vec![SampleFormat::Unsigned],
+                    vec![],
+                ),
+                DynamicImage::ImageLuma8(_) => (
+                    Style::BlackIsZero,
+                    vec![8],
+                    vec![SampleFormat::Unsigned],
+                    vec![],
+                ),
+                DynamicImage::ImageLumaA8(_) => (
+                    Style::BlackIsZero,
+                    vec![8, 8],
+                    vec![SampleFormat::Unsigned; 2],
+                    vec![ExtraSamples::AssociatedAlpha],
+                ),
+                DynamicImage::ImageRgb8(_) => (
+                    Style::RGB,
+                    vec![8, 8, 8],
+                    vec![SampleFormat::Unsigned; 3],
+                    vec![],
+                ),
+                DynamicImage::ImageRgba8(_) => (
+                    Style::RGB,
+                    vec![8, 8, 8, 8],
+                    vec![SampleFormat::Unsigned; 4],
+                    vec![ExtraSamples::AssociatedAlpha],
+                ),
+                DynamicImage::ImageLumaA16(_) => (
+                    Style::BlackIsZero,
+                    vec![16, 16],
+                    vec![SampleFormat::Unsigned; 2],
+                    vec![ExtraSamples::AssociatedAlpha],
+                ),
+                DynamicImage::ImageRgb16(_) => (
+                    Style::RGB,
+                    vec![16, 16, 16],
+                    vec![SampleFormat::Unsigned; 3],
+                    vec![],
+                ),
+                DynamicImage::ImageRgba16(_) => (
+                    Style::RGB,
+                    vec![16, 16, 16, 16],
+                    vec![SampleFormat::Unsigned; 4],
+                    vec![ExtraSamples::AssociatedAlpha],
+                ),
+                DynamicImage::ImageRgb32F(_) => (
+                    Style::RGB,
+                    vec![32, 32, 32],
+                    vec![SampleFormat::Float; 3],
+                    vec![],
+                ),
+                DynamicImage::ImageRgba32F(_) => (
+                    Style::RGB,
+                    vec![32, 32, 32, 32],
+                    vec![SampleFormat::Float; 4],
+                    vec![ExtraSamples::AssociatedAlpha],
+                ),
+                _ => (
+                    Style::Unknown,
+                    vec![8],
+                    vec![SampleFormat::Unsigned],
+                    vec![],
+                ),
+            };
+
+            Self::new(
+                dimensions,
+                buffer,
+                bits_per_sample,
+                interpretation,
+                sample_format,
+                extra_samples,
+            )
+        }
     }
 }
@@ -1,14 +1,21 @@
-use crate::tiff::Endian;
 use std::fmt::Display;
 
+#[cfg(feature = "ndarray")]
+mod array;
 mod image;
 mod ops;
 mod photometrics;
+mod sample;
 
+#[cfg(feature = "ndarray")]
+pub use array::RasterArray;
+pub use half::f16;
 pub use ops::ResizeFilter;
 pub use photometrics::{
     ExtraSamples, PhotometricInterpretation, PlanarConfiguration, SampleFormat,
 };
+pub(crate) use sample::swap_bits;
+pub use sample::{Sample, SampleType};
 
 // TODO
 //  how to deal with odd bit endianness? Have seen it both ways.
@@ -16,6 +23,8 @@ pub use photometrics::{
 #[derive(Debug)]
 pub enum RasterError {
     BufferSize((usize, (u32, u32), Vec<u16>, u32)),
+    MaskSize((usize, (u32, u32))),
+    ColorMapSize((usize, u16)),
     NotSupported(String),
 }
 
@@ -27,8 +36,10 @@ pub struct Raster {
     pub interpretation: PhotometricInterpretation,
     pub sample_format: Vec<SampleFormat>,
     pub extra_samples: Vec<ExtraSamples>,
-    pub endian: Endian,
-    bits_per_pixel: u32, // cached sum of bits_per_sample
+    pub mask: Option<Vec<u8>>,       // one byte per pixel, 0 is invalid
+    pub color_map: Option<Vec<u16>>, // TIFF ColorMap, all reds then greens then blues
+    pub reference_black_white: Option<[f64; 6]>, // YCbCr code ranges
+    bits_per_pixel: u32,             // cached sum of bits_per_sample
 }
 
 impl Raster {
@@ -39,12 +50,10 @@ impl Raster {
         interpretation: PhotometricInterpretation,
         sample_format: Vec<SampleFormat>,
         extra_samples: Vec<ExtraSamples>,
-        endian: Endian,
     ) -> Result<Self, RasterError> {
         let bits_per_pixel = bits_per_sample.iter().sum::<u16>() as u32;
         let bytes_per_pixel = bits_per_pixel / 8;
-        let required_bytes =
-            dimensions.0 as usize * dimensions.1 as usize * bytes_per_pixel as usize;
+        let required_bytes = required_bytes(dimensions, bits_per_pixel);
         if buffer.len() != required_bytes {
             Err(RasterError::BufferSize((
                 buffer.len(),
@@ -60,7 +69,9 @@ impl Raster {
                 interpretation,
                 sample_format,
                 extra_samples,
-                endian,
+                mask: None,
+                color_map: None,
+                reference_black_white: None,
                 bits_per_pixel,
             })
         }
@@ -72,12 +83,9 @@ impl Raster {
         interpretation: PhotometricInterpretation,
         sample_format: Vec<SampleFormat>,
         extra_samples: Vec<ExtraSamples>,
-        endian: Endian,
     ) -> Self {
         let bits_per_pixel = bits_per_sample.iter().sum::<u16>() as u32;
-        let required_bytes =
-            dimensions.0 as usize * dimensions.1 as usize * bits_per_pixel as usize / 8;
-        let buffer = vec![0; required_bytes];
+        let buffer = vec![0; required_bytes(dimensions, bits_per_pixel)];
         Self {
             dimensions,
             buffer,
@@ -85,76 +93,190 @@ impl Raster {
             interpretation,
             sample_format,
             extra_samples,
-            endian,
+            mask: None,
+            color_map: None,
+            reference_black_white: None,
             bits_per_pixel,
         }
     }
 
-    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Vec<u8>> {
-        if x >= self.dimensions.0 || y >= self.dimensions.1 {
+    pub fn with_mask(mut self, mask: Vec<u8>) -> Result<Self, RasterError> {
+        let required = self.dimensions.0 as usize * self.dimensions.1 as usize;
+        if mask.len() != required {
+            return Err(RasterError::MaskSize((mask.len(), self.dimensions)));
+        }
+        self.mask = Some(mask);
+        Ok(self)
+    }
+
+    // Palette for RGBPalette rasters, indexed by the first sample
+    pub fn with_color_map(mut self, color_map: Vec<u16>) -> Result<Self, RasterError> {
+        let bits = self.bits_per_sample.first().copied().unwrap_or(0);
+        if bits > 16 || color_map.len() != 3 << bits {
+            return Err(RasterError::ColorMapSize((color_map.len(), bits)));
+        }
+        self.color_map = Some(color_map);
+        Ok(self)
+    }
+
+    // 16 bit RGB of a palette index
+    pub fn palette_color(&self, index: usize) -> Option<[u16; 3]> {
+        let color_map = self.color_map.as_ref()?;
+        let n = color_map.len() / 3;
+        if index >= n {
             return None;
         }
+        Some([
+            color_map[index],
+            color_map[n + index],
+            color_map[2 * n + index],
+        ])
+    }
 
-        let bytes_per_row = self.row_size();
-        let row_offset: u32 = y * bytes_per_row;
+    // Append an unassociated alpha sample, opaque where the mask is valid
+    pub fn with_alpha_from_mask(self) -> Result<Self, RasterError> {
+        if !self.bits_per_pixel.is_multiple_of(8) {
+            return Err(RasterError::NotSupported(format!(
+                "Pixel is not byte aligned: {} bits",
+                self.bits_per_pixel
+            )));
+        }
+        let bits = self.sample_size()?;
+        let format = self
+            .sample_format
+            .first()
+            .copied()
+            .unwrap_or(SampleFormat::Unsigned);
+        let opaque: Vec<u8> = match (format, bits) {
+            (SampleFormat::Float, 16) => half::f16::ONE.to_ne_bytes().to_vec(),
+            (SampleFormat::Float, 32) => 1f32.to_ne_bytes().to_vec(),
+            (SampleFormat::Float, 64) => 1f64.to_ne_bytes().to_vec(),
+            (SampleFormat::Signed, 8) => i8::MAX.to_ne_bytes().to_vec(),
+            (SampleFormat::Signed, 16) => i16::MAX.to_ne_bytes().to_vec(),
+            (SampleFormat::Signed, 32) => i32::MAX.to_ne_bytes().to_vec(),
+            (SampleFormat::Signed, 64) => i64::MAX.to_ne_bytes().to_vec(),
+            (SampleFormat::Unsigned, 8 | 16 | 32 | 64) => vec![0xFF; bits as usize / 8],
+            _ => {
+                return Err(RasterError::NotSupported(format!(
+                    "Alpha for {format:?} samples of {bits} bits"
+                )))
+            }
+        };
+        let transparent = vec![0; opaque.len()];
 
-        let start_col_offset_bits = x * self.bits_per_pixel;
-        let start_col_offset_bytes = start_col_offset_bits / 8;
-        let start = (row_offset + start_col_offset_bytes) as usize;
+        let bytes_per_pixel = (self.bits_per_pixel / 8) as usize;
+        let pixel_count = self.dimensions.0 as usize * self.dimensions.1 as usize;
+        let mut buffer = Vec::with_capacity(pixel_count * (bytes_per_pixel + opaque.len()));
+        for (i, pixel) in self.buffer.chunks_exact(bytes_per_pixel).enumerate() {
+            buffer.extend_from_slice(pixel);
+            match &self.mask {
+                Some(mask) if mask[i] == 0 => buffer.extend_from_slice(&transparent),
+                _ => buffer.extend_from_slice(&opaque),
+            }
+        }
 
-        let end_col_offset_bits = x * self.bits_per_pixel + self.bits_per_pixel;
-        let end_col_offset_bytes = end_col_offset_bits.div_ceil(8);
-        let end = (row_offset + end_col_offset_bytes) as usize;
+        let mut bits_per_sample = self.bits_per_sample;
+        bits_per_sample.push(bits);
+        let mut sample_format = self.sample_format;
+        sample_format.push(format);
+        let mut extra_samples = self.extra_samples;
+        extra_samples.push(ExtraSamples::UnassociatedAlpha);
+        let raster = Self::new(
+            self.dimensions,
+            buffer,
+            bits_per_sample,
+            self.interpretation,
+            sample_format,
+            extra_samples,
+        )?;
+        let raster = Self {
+            color_map: self.color_map,
+            reference_black_white: self.reference_black_white,
+            ..raster
+        };
+        match self.mask {
+            Some(mask) => raster.with_mask(mask),
+            None => Ok(raster),
+        }
+    }
 
-        let mut pixel = self.buffer[start..end].to_vec();
-        let n = end - start;
+    pub fn is_valid(&self, x: u32, y: u32) -> bool {
+        match &self.mask {
+            Some(mask) => mask
+                .get(y as usize * self.dimensions.0 as usize + x as usize)
+                .is_some_and(|v| *v != 0),
+            None => true,
+        }
+    }
 
-        let start_mask = 0xFF_u8 >> (start_col_offset_bits - start_col_offset_bytes * 8);
-        pixel[0] &= start_mask;
+    // Byte aligned pixels are the raw native endian sample bytes,
+    //   packed pixels are right aligned in the fewest big endian bytes
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Vec<u8>> {
+        if x >= self.dimensions.0 || y >= self.dimensions.1 {
+            return None;
+        }
+        let row_offset = y as usize * self.row_size() as usize;
+        let bits = self.bits_per_pixel as usize;
 
-        let end_mask =
-            ((0xFF_u16 << (end_col_offset_bytes * 8 - end_col_offset_bits)) & 0xFF_u16) as u8;
-        pixel[n - 1] &= end_mask;
+        if bits.is_multiple_of(8) {
+            let start = row_offset + x as usize * bits / 8;
+            return self.buffer.get(start..start + bits / 8).map(|p| p.to_vec());
+        }
 
-        Some(pixel)
+        if bits > 64 {
+            return None;
+        }
+        let mut value: u64 = 0;
+        for bit in x as usize * bits..(x as usize + 1) * bits {
+            let byte = self.buffer.get(row_offset + bit / 8)?;
+            value = (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u64;
+        }
+        Some(value.to_be_bytes()[8 - bits.div_ceil(8)..].to_vec())
     }
 
     pub fn put_pixel(&mut self, x: u32, y: u32, pixel: Vec<u8>) -> Result<(), String> {
         if x >= self.dimensions.0 || y >= self.dimensions.1 {
             return Err("Bad pixel index".into());
         }
+        let row_offset = y as usize * self.row_size() as usize;
+        let bits = self.bits_per_pixel as usize;
 
-        let bytes_per_row = self.row_size();
-        let row_offset: u32 = y * bytes_per_row;
-
-        let start_col_offset_bits = x * self.bits_per_pixel;
-        let start_col_offset_bytes = start_col_offset_bits / 8;
-        let start = (row_offset + start_col_offset_bytes) as usize;
-
-        let end_col_offset_bits = x * self.bits_per_pixel + self.bits_per_pixel;
-        let end_col_offset_bytes = end_col_offset_bits.div_ceil(8);
-        let end = (row_offset + end_col_offset_bytes) as usize;
-
-        let n = end - start;
-
-        if pixel.len() != n {
+        if pixel.len() != bits.div_ceil(8) {
             return Err("Bad pixel size".into());
         }
 
-        let start_mask = 0xFF_u8 >> (start_col_offset_bits - start_col_offset_bytes * 8);
-        self.buffer[start] = (self.buffer[start] & !start_mask) | (pixel[0] & start_mask);
-
-        if n > 1 {
-            let end_mask =
-                ((0xFF_u16 << (end_col_offset_bytes * 8 - end_col_offset_bits)) & 0xFF_u16) as u8;
-            self.buffer[end - 1] = (self.buffer[end - 1] & !end_mask) | (pixel[n - 1] & end_mask);
+        if bits.is_multiple_of(8) {
+            let start = row_offset + x as usize * bits / 8;
+            self.buffer
+                .get_mut(start..start + pixel.len())
+                .ok_or("Bad pixel index")?
+                .copy_from_slice(&pixel);
+            return Ok(());
         }
 
-        self.buffer[start..(n + start)].copy_from_slice(&pixel[..n]);
-
+        if bits > 64 {
+            return Err("Bad pixel size".into());
+        }
+        let value = pixel.iter().fold(0u64, |v, b| (v << 8) | *b as u64);
+        for (i, bit) in (x as usize * bits..(x as usize + 1) * bits).enumerate() {
+            let byte = self
+                .buffer
+                .get_mut(row_offset + bit / 8)
+                .ok_or("Bad pixel index")?;
+            let mask = 0x80 >> (bit % 8);
+            match (value >> (bits - 1 - i)) & 1 {
+                0 => *byte &= !mask,
+                _ => *byte |= mask,
+            }
+        }
         Ok(())
     }
 
+    // Whether pixels are whole bytes, otherwise they are packed with rows padded to a byte
+    pub fn is_byte_aligned(&self) -> bool {
+        self.bits_per_pixel.is_multiple_of(8)
+    }
+
     pub fn row_size(&self) -> u32 {
         (self.dimensions.0 * self.bits_per_pixel).div_ceil(8)
     }
@@ -174,17 +296,21 @@ impl Raster {
     }
 }
 
+// Rows are padded to a byte boundary
+fn required_bytes(dimensions: (u32, u32), bits_per_pixel: u32) -> usize {
+    (dimensions.0 as usize * bits_per_pixel as usize).div_ceil(8) * dimensions.1 as usize
+}
+
 impl Display for Raster {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Raster({}x{}, {:?}, {:?}, {}Bytes, {:?} Endian)",
+            "Raster({}x{}, {:?}, {:?}, {}Bytes)",
             self.dimensions.0,
             self.dimensions.1,
             self.bits_per_sample,
             self.interpretation,
-            self.buffer.len(),
-            self.endian
+            self.buffer.len()
         )
     }
 }
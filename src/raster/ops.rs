@@ -16,14 +16,15 @@ impl Raster {
         height: u32,
         filter: ResizeFilter,
     ) -> Result<Self, RasterError> {
-        if !self.bits_per_pixel.is_multiple_of(8) {
-            return Err(RasterError::NotSupported(format!(
-                "Pixel is not byte aligned: {} bits",
-                self.bits_per_pixel
-            )));
+        if !self.is_byte_aligned() {
+            return self.resize_packed(width, height, filter);
         }
         let bytes_per_pixel = (self.bits_per_pixel / 8) as usize;
-        let mut buffer = vec![0; ((width * height) as usize) * bytes_per_pixel];
+        let mut buffer = vec![0; width as usize * height as usize * bytes_per_pixel];
+        let mut mask = self
+            .mask
+            .as_ref()
+            .map(|_| vec![0; width as usize * height as usize]);
 
         let full_width = self.dimensions.0 as f32;
         let full_height = self.dimensions.1 as f32;
@@ -34,10 +35,13 @@ impl Raster {
                     let v = (j as f32 * scale.1) as u32;
                     for i in 0..width {
                         let u = (i as f32 * scale.0) as u32;
-                        let src = (v * self.dimensions.0 + u) as usize * bytes_per_pixel;
-                        let dst = (j * width + i) as usize * bytes_per_pixel;
+                        let src = index(u, v, self.dimensions.0) * bytes_per_pixel;
+                        let dst = index(i, j, width) * bytes_per_pixel;
                         buffer[dst..dst + bytes_per_pixel]
                             .copy_from_slice(&self.buffer[src..src + bytes_per_pixel]);
+                        if let (Some(mask), Some(src_mask)) = (&mut mask, &self.mask) {
+                            mask[index(i, j, width)] = src_mask[index(u, v, self.dimensions.0)];
+                        }
                     }
                 }
             }
@@ -55,18 +59,25 @@ impl Raster {
                     for i in 0..width {
                         let u_start = (i as f32 * scale.0) as u32;
                         let u_end = ((i + 1) as f32 * scale.0) as u32;
-                        let dst = (j * width + i) as usize * bytes_per_pixel;
+                        let dst = index(i, j, width) * bytes_per_pixel;
                         for s in 0..samples {
                             let mut value: u8 = 0;
                             for v in v_start..v_end {
                                 for u in u_start..u_end {
-                                    let src =
-                                        (v * self.dimensions.0 + u) as usize * bytes_per_pixel;
+                                    let src = index(u, v, self.dimensions.0) * bytes_per_pixel;
                                     value = value.max(self.buffer[src + s]);
                                 }
                             }
                             buffer[dst + s] = value;
                         }
+                        if let (Some(mask), Some(src_mask)) = (&mut mask, &self.mask) {
+                            for v in v_start..v_end {
+                                for u in u_start..u_end {
+                                    let valid = src_mask[index(u, v, self.dimensions.0)];
+                                    mask[index(i, j, width)] = mask[index(i, j, width)].max(valid);
+                                }
+                            }
+                        }
                     }
                 }
             }
@@ -84,46 +95,145 @@ impl Raster {
                 return Raster::from_image(&img_resized);
             }
         }
-        Self::new(
+        let raster = Self::new(
             (width, height),
             buffer,
             self.bits_per_sample.clone(),
             self.interpretation,
             self.sample_format.clone(),
             self.extra_samples.clone(),
-            self.endian,
-        )
+        )?;
+        let raster = Self {
+            color_map: self.color_map.clone(),
+            reference_black_white: self.reference_black_white,
+            ..raster
+        };
+        match mask {
+            Some(mask) => raster.with_mask(mask),
+            None => Ok(raster),
+        }
     }
 
+    // Pixels of the region outside of the raster are left blank
     pub fn get_region(&self, region: Region<u32>) -> Result<Self, RasterError> {
-        if !self.bits_per_pixel.is_multiple_of(8) {
-            return Err(RasterError::NotSupported(format!(
-                "Pixel is not byte aligned: {} bits",
-                self.bits_per_pixel
-            )));
-        }
-        let bytes_per_pixel = (self.bits_per_pixel / 8) as usize;
         let width = region.x.range();
         let height = region.y.range();
-        let mut buffer = vec![0; ((width * height) as usize) * bytes_per_pixel];
+        let mut raster = self.blank_like((width, height));
+        let mut mask = self
+            .mask
+            .as_ref()
+            .map(|_| vec![0; width as usize * height as usize]);
 
-        for j in region.y.min..region.y.max.min(self.dimensions.1 - 1) {
-            for i in region.x.min..region.x.max.min(self.dimensions.0 - 1) {
-                let src = (j * self.dimensions.0 + i) as usize * bytes_per_pixel;
-                let dst =
-                    ((j - region.y.min) * width + i - region.x.min) as usize * bytes_per_pixel;
-                let pixel = &self.buffer[src..src + bytes_per_pixel];
-                buffer[dst..dst + bytes_per_pixel].copy_from_slice(pixel);
+        let x_max = region.x.max.min(self.dimensions.0);
+        for j in region.y.min..region.y.max.min(self.dimensions.1) {
+            if region.x.min >= x_max {
+                break;
+            }
+            let y = j - region.y.min;
+            if self.is_byte_aligned() {
+                let bytes_per_pixel = (self.bits_per_pixel / 8) as usize;
+                let src = index(region.x.min, j, self.dimensions.0) * bytes_per_pixel;
+                let dst = index(0, y, width) * bytes_per_pixel;
+                let n = (x_max - region.x.min) as usize * bytes_per_pixel;
+                raster.buffer[dst..dst + n].copy_from_slice(&self.buffer[src..src + n]);
+            } else {
+                for i in region.x.min..x_max {
+                    if let Some(pixel) = self.get_pixel(i, j) {
+                        raster
+                            .put_pixel(i - region.x.min, y, pixel)
+                            .map_err(RasterError::NotSupported)?;
+                    }
+                }
+            }
+            if let (Some(mask), Some(src_mask)) = (&mut mask, &self.mask) {
+                let src = index(region.x.min, j, self.dimensions.0);
+                let dst = index(0, y, width);
+                let n = (x_max - region.x.min) as usize;
+                mask[dst..dst + n].copy_from_slice(&src_mask[src..src + n]);
             }
         }
-        Self::new(
-            (width, height),
-            buffer,
+        match mask {
+            Some(mask) => raster.with_mask(mask),
+            None => Ok(raster),
+        }
+    }
+
+    // Resize of packed sub-byte pixels, one pixel at a time
+    fn resize_packed(
+        &self,
+        width: u32,
+        height: u32,
+        filter: ResizeFilter,
+    ) -> Result<Self, RasterError> {
+        let mut raster = self.blank_like((width, height));
+        let mut mask = self
+            .mask
+            .as_ref()
+            .map(|_| vec![0; width as usize * height as usize]);
+        let scale = (
+            self.dimensions.0 as f32 / width as f32,
+            self.dimensions.1 as f32 / height as f32,
+        );
+        for j in 0..height {
+            let v_start = (j as f32 * scale.1) as u32;
+            for i in 0..width {
+                let u_start = (i as f32 * scale.0) as u32;
+                let (u_end, v_end) = match filter {
+                    ResizeFilter::Nearest => (u_start + 1, v_start + 1),
+                    ResizeFilter::Maximum if self.bits_per_sample.len() == 1 => (
+                        (((i + 1) as f32 * scale.0) as u32).max(u_start + 1),
+                        (((j + 1) as f32 * scale.1) as u32).max(v_start + 1),
+                    ),
+                    _ => {
+                        return Err(RasterError::NotSupported(format!(
+                            "{filter:?} of packed {:?} bit samples",
+                            self.bits_per_sample
+                        )))
+                    }
+                };
+                // Packed pixels are big endian, so byte order is numeric order
+                let mut value: Option<Vec<u8>> = None;
+                let mut valid = 0;
+                for v in v_start..v_end.min(self.dimensions.1) {
+                    for u in u_start..u_end.min(self.dimensions.0) {
+                        value = value.max(self.get_pixel(u, v));
+                        if let Some(src_mask) = &self.mask {
+                            valid = valid.max(src_mask[index(u, v, self.dimensions.0)]);
+                        }
+                    }
+                }
+                if let Some(value) = value {
+                    raster
+                        .put_pixel(i, j, value)
+                        .map_err(RasterError::NotSupported)?;
+                }
+                if let Some(mask) = &mut mask {
+                    mask[index(i, j, width)] = valid;
+                }
+            }
+        }
+        match mask {
+            Some(mask) => raster.with_mask(mask),
+            None => Ok(raster),
+        }
+    }
+
+    // Blank raster with the same samples and photometrics
+    fn blank_like(&self, dimensions: (u32, u32)) -> Self {
+        let mut raster = Self::blank(
+            dimensions,
             self.bits_per_sample.clone(),
             self.interpretation,
             self.sample_format.clone(),
             self.extra_samples.clone(),
-            self.endian,
-        )
+        );
+        raster.color_map = self.color_map.clone();
+        raster.reference_black_white = self.reference_black_white;
+        raster
     }
 }
+
+// Position of pixel (x, y) in a row major raster, computed in usize to not overflow
+fn index(x: u32, y: u32, width: u32) -> usize {
+    y as usize * width as usize + x as usize
+}
@@ -0,0 +1,412 @@
+// Typed samples for every SampleFormat and bit depth combination
+//   https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf (Section 19)
+//   https://www.awaresystems.be/imaging/tiff/tifftags/sampleformat.html
+//   Complex samples are a real part followed by an imaginary part, each byte swapped separately.
+//   24 bit floats are from Adobe Photoshop TIFF Technical Note 3, with 7 exponent bits.
+
+use super::{PhotometricInterpretation, Raster, RasterError, SampleFormat};
+use half::f16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F16,
+    F24,
+    F32,
+    F64,
+    ComplexI16,
+    ComplexI32,
+    ComplexF16,
+    ComplexF32,
+    ComplexF64,
+}
+
+impl SampleType {
+    pub fn from_format(sample_format: SampleFormat, bits: u16) -> Option<Self> {
+        Some(match (sample_format, bits) {
+            (SampleFormat::Signed, 8) => Self::I8,
+            (SampleFormat::Signed, 16) => Self::I16,
+            (SampleFormat::Signed, 32) => Self::I32,
+            (SampleFormat::Signed, 64) => Self::I64,
+            (SampleFormat::Float, 16) => Self::F16,
+            (SampleFormat::Float, 24) => Self::F24,
+            (SampleFormat::Float, 32) => Self::F32,
+            (SampleFormat::Float, 64) => Self::F64,
+            (SampleFormat::ComplexInt, 32) => Self::ComplexI16,
+            (SampleFormat::ComplexInt, 64) => Self::ComplexI32,
+            (SampleFormat::ComplexFloat, 32) => Self::ComplexF16,
+            (SampleFormat::ComplexFloat, 64) => Self::ComplexF32,
+            (SampleFormat::ComplexFloat, 128) => Self::ComplexF64,
+            (SampleFormat::Signed | SampleFormat::Float, _) => return None,
+            (SampleFormat::ComplexInt | SampleFormat::ComplexFloat, _) => return None,
+            // Unsigned, Undefined and Unknown are all read as unsigned
+            (_, 8) => Self::U8,
+            (_, 16) => Self::U16,
+            (_, 32) => Self::U32,
+            (_, 64) => Self::U64,
+            _ => return None,
+        })
+    }
+
+    pub fn sample_format(&self) -> SampleFormat {
+        match self {
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 => SampleFormat::Unsigned,
+            Self::I8 | Self::I16 | Self::I32 | Self::I64 => SampleFormat::Signed,
+            Self::F16 | Self::F24 | Self::F32 | Self::F64 => SampleFormat::Float,
+            Self::ComplexI16 | Self::ComplexI32 => SampleFormat::ComplexInt,
+            Self::ComplexF16 | Self::ComplexF32 | Self::ComplexF64 => SampleFormat::ComplexFloat,
+        }
+    }
+
+    pub fn bits(&self) -> u16 {
+        match self {
+            Self::U8 | Self::I8 => 8,
+            Self::U16 | Self::I16 | Self::F16 => 16,
+            Self::F24 => 24,
+            Self::U32 | Self::I32 | Self::F32 | Self::ComplexI16 | Self::ComplexF16 => 32,
+            Self::U64 | Self::I64 | Self::F64 | Self::ComplexI32 | Self::ComplexF32 => 64,
+            Self::ComplexF64 => 128,
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bits() as usize / 8
+    }
+
+    pub fn is_signed(&self) -> bool {
+        !matches!(self, Self::U8 | Self::U16 | Self::U32 | Self::U64)
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            Self::F16
+                | Self::F24
+                | Self::F32
+                | Self::F64
+                | Self::ComplexF16
+                | Self::ComplexF32
+                | Self::ComplexF64
+        )
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(
+            self,
+            Self::ComplexI16
+                | Self::ComplexI32
+                | Self::ComplexF16
+                | Self::ComplexF32
+                | Self::ComplexF64
+        )
+    }
+
+    /// Bits of each byte swapped part, half the sample for complex types
+    pub fn component_bits(&self) -> u16 {
+        match self.is_complex() {
+            true => self.bits() / 2,
+            false => self.bits(),
+        }
+    }
+
+    /// Real and imaginary parts of a native endian sample, imaginary is 0 for real types
+    pub fn to_complex(&self, bytes: &[u8]) -> Option<(f64, f64)> {
+        let bytes = bytes.get(..self.bytes())?;
+        let (re, im) = bytes.split_at(bytes.len() / 2);
+        Some(match self {
+            Self::U8 => (bytes[0] as f64, 0.0),
+            Self::U16 => (u16::from_ne_bytes(array(bytes)) as f64, 0.0),
+            Self::U32 => (u32::from_ne_bytes(array(bytes)) as f64, 0.0),
+            Self::U64 => (u64::from_ne_bytes(array(bytes)) as f64, 0.0),
+            Self::I8 => (bytes[0] as i8 as f64, 0.0),
+            Self::I16 => (i16::from_ne_bytes(array(bytes)) as f64, 0.0),
+            Self::I32 => (i32::from_ne_bytes(array(bytes)) as f64, 0.0),
+            Self::I64 => (i64::from_ne_bytes(array(bytes)) as f64, 0.0),
+            Self::F16 => (f16::from_ne_bytes(array(bytes)).to_f64(), 0.0),
+            Self::F24 => (f24_to_f64(bytes), 0.0),
+            Self::F32 => (f32::from_ne_bytes(array(bytes)) as f64, 0.0),
+            Self::F64 => (f64::from_ne_bytes(array(bytes)), 0.0),
+            Self::ComplexI16 => (
+                i16::from_ne_bytes(array(re)) as f64,
+                i16::from_ne_bytes(array(im)) as f64,
+            ),
+            Self::ComplexI32 => (
+                i32::from_ne_bytes(array(re)) as f64,
+                i32::from_ne_bytes(array(im)) as f64,
+            ),
+            Self::ComplexF16 => (
+                f16::from_ne_bytes(array(re)).to_f64(),
+                f16::from_ne_bytes(array(im)).to_f64(),
+            ),
+            Self::ComplexF32 => (
+                f32::from_ne_bytes(array(re)) as f64,
+                f32::from_ne_bytes(array(im)) as f64,
+            ),
+            Self::ComplexF64 => (f64::from_ne_bytes(array(re)), f64::from_ne_bytes(array(im))),
+        })
+    }
+
+    /// Value of a native endian sample, the magnitude for complex types
+    pub fn to_f64(&self, bytes: &[u8]) -> Option<f64> {
+        let (re, im) = self.to_complex(bytes)?;
+        Some(match self.is_complex() {
+            true => re.hypot(im),
+            false => re,
+        })
+    }
+
+    /// Fixed values shown as black and white, 0 to 1 for floats
+    ///   Raster::to_rgba8 stretches signed and float samples to their actual range instead
+    pub fn display_range(&self) -> (f64, f64) {
+        match self {
+            Self::U8 => (0.0, u8::MAX as f64),
+            Self::U16 => (0.0, u16::MAX as f64),
+            Self::U32 => (0.0, u32::MAX as f64),
+            Self::U64 => (0.0, u64::MAX as f64),
+            // Negative values are shown as black
+            Self::I8 => (0.0, i8::MAX as f64),
+            Self::I16 | Self::ComplexI16 => (0.0, i16::MAX as f64),
+            Self::I32 | Self::ComplexI32 => (0.0, i32::MAX as f64),
+            Self::I64 => (0.0, i64::MAX as f64),
+            _ => (0.0, 1.0),
+        }
+    }
+}
+
+impl Raster {
+    /// Sample type of each sample, packed samples are not typed
+    pub fn sample_types(&self) -> Result<Vec<SampleType>, RasterError> {
+        self.bits_per_sample
+            .iter()
+            .enumerate()
+            .map(|(i, bits)| {
+                let format = self
+                    .sample_format
+                    .get(i)
+                    .copied()
+                    .unwrap_or(SampleFormat::Unsigned);
+                SampleType::from_format(format, *bits).ok_or_else(|| {
+                    RasterError::NotSupported(format!("{format:?} samples of {bits} bits"))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Primitive sample values, converted from other types with saturation
+pub trait Sample: Copy + Default + Send + Sync + 'static {
+    const TYPE: SampleType;
+
+    fn from_ne_slice(bytes: &[u8]) -> Self;
+    fn extend_ne_bytes(self, buffer: &mut Vec<u8>);
+    fn to_f64(self) -> f64;
+
+    /// Rounded and clamped to the type's range, NaN is 0 for integers
+    fn from_f64(value: f64) -> Self;
+}
+
+macro_rules! impl_sample {
+    ($t:ty, $sample_type:expr, $round:expr) => {
+        impl Sample for $t {
+            const TYPE: SampleType = $sample_type;
+
+            fn from_ne_slice(bytes: &[u8]) -> Self {
+                <$t>::from_ne_bytes(array(bytes))
+            }
+
+            fn extend_ne_bytes(self, buffer: &mut Vec<u8>) {
+                buffer.extend_from_slice(&self.to_ne_bytes());
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f64(value: f64) -> Self {
+                // Float to integer casts saturate
+                $round(value) as $t
+            }
+        }
+    };
+}
+
+impl_sample!(u8, SampleType::U8, f64::round);
+impl_sample!(u16, SampleType::U16, f64::round);
+impl_sample!(u32, SampleType::U32, f64::round);
+impl_sample!(u64, SampleType::U64, f64::round);
+impl_sample!(i8, SampleType::I8, f64::round);
+impl_sample!(i16, SampleType::I16, f64::round);
+impl_sample!(i32, SampleType::I32, f64::round);
+impl_sample!(i64, SampleType::I64, f64::round);
+impl_sample!(f32, SampleType::F32, std::convert::identity);
+impl_sample!(f64, SampleType::F64, std::convert::identity);
+
+impl Sample for f16 {
+    const TYPE: SampleType = SampleType::F16;
+
+    fn from_ne_slice(bytes: &[u8]) -> Self {
+        f16::from_ne_bytes(array(bytes))
+    }
+
+    fn extend_ne_bytes(self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_ne_bytes());
+    }
+
+    fn to_f64(self) -> f64 {
+        f16::to_f64(self)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        f16::from_f64(value)
+    }
+}
+
+impl Raster {
+    pub fn from_samples<T: Sample>(
+        dimensions: (u32, u32),
+        samples: Vec<T>,
+        samples_per_pixel: usize,
+        interpretation: PhotometricInterpretation,
+    ) -> Result<Self, RasterError> {
+        let mut buffer = Vec::with_capacity(samples.len() * T::TYPE.bytes());
+        for sample in samples {
+            sample.extend_ne_bytes(&mut buffer);
+        }
+        Self::new(
+            dimensions,
+            buffer,
+            vec![T::TYPE.bits(); samples_per_pixel],
+            interpretation,
+            vec![T::TYPE.sample_format(); samples_per_pixel],
+            vec![],
+        )
+    }
+
+    /// Every sample in pixel order, converted to T when the raster has another type.
+    /// Complex samples are converted by magnitude.
+    pub fn samples<T: Sample>(&self) -> Result<Vec<T>, RasterError> {
+        let pixel_count = self.dimensions.0 as usize * self.dimensions.1 as usize;
+        let mut samples = Vec::with_capacity(pixel_count * self.bits_per_sample.len());
+
+        // Same type, no conversion
+        if let Ok(sample_types) = self.sample_types() {
+            if sample_types.iter().all(|t| *t == T::TYPE) {
+                let size = T::TYPE.bytes();
+                samples.extend(self.buffer.chunks_exact(size).map(T::from_ne_slice));
+                return Ok(samples);
+            }
+        }
+
+        let unsupported = || {
+            RasterError::NotSupported(format!(
+                "Samples of {:?} with bits per sample {:?}",
+                self.sample_format, self.bits_per_sample
+            ))
+        };
+        for y in 0..self.dimensions.1 {
+            for x in 0..self.dimensions.0 {
+                let pixel = self.get_pixel_f64(x, y).ok_or_else(unsupported)?;
+                samples.extend(pixel.into_iter().map(T::from_f64));
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Samples of one band in pixel order
+    pub fn band<T: Sample>(&self, band: usize) -> Result<Vec<T>, RasterError> {
+        let bands = self.bits_per_sample.len();
+        if band >= bands {
+            return Err(RasterError::NotSupported(format!(
+                "Band {band} of a raster with {bands} bands"
+            )));
+        }
+        let samples = self.samples::<T>()?;
+        Ok(samples.into_iter().skip(band).step_by(bands).collect())
+    }
+
+    pub fn bands<T: Sample>(&self) -> Result<Vec<Vec<T>>, RasterError> {
+        let bands = self.bits_per_sample.len();
+        let samples = self.samples::<T>()?;
+        let mut output = vec![Vec::with_capacity(samples.len() / bands.max(1)); bands];
+        for (i, sample) in samples.into_iter().enumerate() {
+            output[i % bands].push(sample);
+        }
+        Ok(output)
+    }
+
+    /// Sample values of a pixel, the magnitude of complex samples
+    pub fn get_pixel_f64(&self, x: u32, y: u32) -> Option<Vec<f64>> {
+        let pixel = self.get_pixel(x, y)?;
+        self.pixel_samples(&pixel)
+    }
+
+    pub fn get_sample_f64(&self, x: u32, y: u32, band: usize) -> Option<f64> {
+        self.get_pixel_f64(x, y)?.get(band).copied()
+    }
+
+    /// Raster with every sample converted to T with saturation
+    pub fn convert<T: Sample>(&self) -> Result<Self, RasterError> {
+        let bands = self.bits_per_sample.len();
+        let samples = self.samples::<T>()?;
+        let raster = Self::from_samples(self.dimensions, samples, bands, self.interpretation)?;
+        let bits_unchanged = self
+            .bits_per_sample
+            .iter()
+            .all(|bits| *bits == T::TYPE.bits());
+        let raster = Self {
+            extra_samples: self.extra_samples.clone(),
+            // Palette size depends on the bit depth
+            color_map: self.color_map.clone().filter(|_| bits_unchanged),
+            reference_black_white: self.reference_black_white,
+            ..raster
+        };
+        match &self.mask {
+            Some(mask) => raster.with_mask(mask.clone()),
+            None => Ok(raster),
+        }
+    }
+}
+
+// Bits of the byte swapped parts of each sample, splitting complex samples in two
+pub(crate) fn swap_bits(bits_per_sample: &[u16], sample_format: &[SampleFormat]) -> Vec<u16> {
+    let mut bits = Vec::with_capacity(bits_per_sample.len());
+    for (i, sample_bits) in bits_per_sample.iter().enumerate() {
+        match sample_format.get(i) {
+            Some(SampleFormat::ComplexInt | SampleFormat::ComplexFloat) => {
+                bits.extend([sample_bits / 2; 2])
+            }
+            _ => bits.push(*sample_bits),
+        }
+    }
+    bits
+}
+
+// 1 sign bit, 7 exponent bits with a bias of 63, 16 mantissa bits
+fn f24_to_f64(bytes: &[u8]) -> f64 {
+    let v = match cfg!(target_endian = "big") {
+        true => u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]),
+        false => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]),
+    };
+    let sign = if v & 0x80_0000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((v >> 16) & 0x7F) as i32;
+    let mantissa = (v & 0xFFFF) as f64 / 65536.0;
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-62),
+        0x7F if mantissa == 0.0 => f64::INFINITY,
+        0x7F => f64::NAN,
+        _ => (1.0 + mantissa) * 2f64.powi(exponent - 63),
+    }
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut array = [0; N];
+    array.copy_from_slice(&bytes[..N]);
+    array
+}
@@ -0,0 +1,86 @@
+use crate::cog::CloudTiffResult;
+use crate::encode::{EncodeResult, Encoder};
+use crate::projection::Projection;
+use crate::raster::Raster;
+use crate::Region;
+
+// Raster with the projection of its pixels
+//   The projection's origin is the top left corner of the raster and its scale is the full extent,
+//   in the units of proj4rs (radians for geographic coordinates).
+#[derive(Clone, Debug)]
+pub struct GeoRaster {
+    pub raster: Raster,
+    pub projection: Projection,
+}
+
+impl GeoRaster {
+    pub fn new(raster: Raster, projection: Projection) -> Self {
+        Self { raster, projection }
+    }
+
+    pub fn epsg(&self) -> u16 {
+        self.projection.epsg
+    }
+
+    /// GDAL style geotransform in GeoTIFF units (degrees for geographic coordinates)
+    ///   x = t[0] + col * t[1] + row * t[2]
+    ///   y = t[3] + col * t[4] + row * t[5]
+    pub fn geo_transform(&self) -> [f64; 6] {
+        let region = self.region();
+        let (width, height) = self.raster.dimensions;
+        [
+            region.x.min,
+            region.x.range() / width as f64,
+            0.0,
+            region.y.max,
+            0.0,
+            -region.y.range() / height as f64,
+        ]
+    }
+
+    /// Extent in GeoTIFF units, as taken by Encoder::with_projection
+    pub fn region(&self) -> Region<f64> {
+        let Projection {
+            origin,
+            scale,
+            proj,
+            ..
+        } = &self.projection;
+        let region = Region::new(origin.0, origin.1 - scale.1, origin.0 + scale.0, origin.1);
+        match proj.is_latlong() {
+            true => region * 1f64.to_degrees(),
+            false => region,
+        }
+    }
+
+    /// Pixel coordinates of a point, which may be outside of the raster
+    pub fn pixel_from(&self, x: f64, y: f64, epsg: u16) -> CloudTiffResult<(f64, f64)> {
+        let (u, v, _) = self.projection.transform_from(x, y, 0.0, epsg)?;
+        let (width, height) = self.raster.dimensions;
+        Ok((u * width as f64, v * height as f64))
+    }
+
+    pub fn pixel_from_lat_lon_deg(&self, lat: f64, lon: f64) -> CloudTiffResult<(f64, f64)> {
+        self.pixel_from(lon.to_radians(), lat.to_radians(), 4326)
+    }
+
+    /// Point at pixel coordinates, the top left corner of pixel (0, 0) is (0.0, 0.0)
+    pub fn point_into(&self, i: f64, j: f64, epsg: u16) -> CloudTiffResult<(f64, f64)> {
+        let (width, height) = self.raster.dimensions;
+        let (u, v) = (i / width as f64, j / height as f64);
+        let (x, y, _) = self.projection.transform_into(u, v, 0.0, epsg)?;
+        Ok((x, y))
+    }
+
+    pub fn get_pixel_at(&self, x: f64, y: f64, epsg: u16) -> CloudTiffResult<Option<Vec<u8>>> {
+        let (i, j) = self.pixel_from(x, y, epsg)?;
+        if i < 0.0 || j < 0.0 {
+            return Ok(None);
+        }
+        Ok(self.raster.get_pixel(i as u32, j as u32))
+    }
+
+    pub fn into_encoder(self) -> EncodeResult<Encoder> {
+        Encoder::from_geo_raster(self)
+    }
+}
@@ -1,13 +1,18 @@
 use crate::cog::{CloudTiff, CloudTiffResult};
 use crate::projection::Projection;
-use crate::{AsyncReadRange, ReadRange, Region, UnitFloat};
+use crate::{ReadRange, Region, UnitFloat};
 
+#[cfg(feature = "async")]
+use crate::AsyncReadRange;
+
+mod georaster;
 mod not_sync;
 mod renderer;
 mod sync;
 pub mod tiles;
 pub mod util;
 
+pub use georaster::GeoRaster;
 pub use sync::SyncRender;
 
 #[cfg(feature = "async")]
@@ -23,6 +28,16 @@ pub struct RenderBuilder<'c> {
     pub input_projection: Projection,
     pub region: RenderRegion,
     pub resolution: (u32, u32),
+    pub bands: Option<Vec<usize>>,
+    pub nodata: NoDataRender,
+}
+
+// How pixels that are nodata, masked out or outside of the image appear in a render
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum NoDataRender {
+    #[default]
+    Untouched, // left blank, see Raster::mask
+    Alpha, // appends an alpha band which is transparent over invalid pixels
 }
 
 #[derive(Debug)]
@@ -39,6 +54,8 @@ impl CloudTiff {
             input_projection: self.projection.clone(),
             region: RenderRegion::InputCrop(Region::unit()),
             resolution: self.full_dimensions(),
+            bands: None,
+            nodata: NoDataRender::default(),
         }
     }
 }
@@ -101,6 +118,17 @@ impl<'c> RenderBuilder<'c> {
         self
     }
 
+    // Only fetch these bands, requires a planar level
+    pub fn with_bands(mut self, bands: Vec<usize>) -> Self {
+        self.bands = Some(bands);
+        self
+    }
+
+    pub fn with_nodata(mut self, nodata: NoDataRender) -> Self {
+        self.nodata = nodata;
+        self
+    }
+
     pub fn of_crop(mut self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
         self.region = RenderRegion::InputCrop(Region::new_saturated(min_x, min_y, max_x, max_y));
         self
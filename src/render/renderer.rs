@@ -14,11 +14,11 @@ pub fn render_image_crop_from_tile_cache(
     let mut render_raster = Raster::blank(
         *dimensions,
         level.bits_per_sample.clone(),
-        level.interpretation,
+        level.tile_interpretation(),
         level.sample_format.clone(),
         level.extra_samples.clone(),
-        level.endian,
     );
+    let mut mask = vec![0; dimensions.0 as usize * dimensions.1 as usize];
     let dxdi = crop.x.range().as_f64() / dimensions.0 as f64;
     let mut y = crop.y.min.as_f64();
     let dydj = crop.y.range().as_f64() / dimensions.1 as f64;
@@ -27,8 +27,13 @@ pub fn render_image_crop_from_tile_cache(
         for i in 0..dimensions.0 {
             if let Ok((tile_index, u, v)) = level.index_from_image_coords(x, y) {
                 if let Some(tile) = tile_cache.get(&tile_index) {
-                    if let Some(pixel) = tile.get_pixel(u as u32, v as u32) {
-                        let _ = render_raster.put_pixel(i, j, pixel);
+                    // Masked pixels are left blank
+                    if tile.is_valid(u as u32, v as u32) {
+                        if let Some(pixel) = tile.get_pixel(u as u32, v as u32) {
+                            if render_raster.put_pixel(i, j, pixel).is_ok() {
+                                mask[(j * dimensions.0 + i) as usize] = 255;
+                            }
+                        }
                     }
                 }
             }
@@ -36,6 +41,9 @@ pub fn render_image_crop_from_tile_cache(
         }
         y += dydj;
     }
+    render_raster.mask = Some(mask);
+    render_raster.color_map = level.color_map.clone(); // Validated with the level
+    render_raster.reference_black_white = level.reference_black_white;
     render_raster
 }
 
@@ -48,20 +56,25 @@ pub fn render_pixel_map(
     let mut render_raster = Raster::blank(
         *dimensions,
         level.bits_per_sample.clone(),
-        level.interpretation,
+        level.tile_interpretation(),
         level.sample_format.clone(),
         level.extra_samples.clone(),
-        level.endian,
     );
+    let mut mask = vec![0; dimensions.0 as usize * dimensions.1 as usize];
     for (tile_index, tile_pixel_map) in pixel_map.iter() {
         if let Some(tile) = tile_cache.get(tile_index) {
             for (from, to) in tile_pixel_map {
                 // TODO interpolation methods other than "floor"
+                if !tile.is_valid(from.0 as u32, from.1 as u32) {
+                    continue;
+                }
                 if let Some(pixel) = tile.get_pixel(from.0 as u32, from.1 as u32) {
-                    let _ = render_raster.put_pixel(to.0, to.1, pixel);
+                    if render_raster.put_pixel(to.0, to.1, pixel).is_ok() {
+                        mask[(to.1 * dimensions.0 + to.0) as usize] = 255;
+                    }
                 }
             }
         }
     }
-    Ok(render_raster)
+    level.attach_photometrics(render_raster.with_mask(mask)?)
 }
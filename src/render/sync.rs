@@ -1,9 +1,13 @@
 use super::renderer;
 use super::CloudTiffResult;
 use super::{tiles, util};
-use super::{RenderBuilder, RenderRegion};
+use super::{GeoRaster, RenderBuilder, RenderRegion};
 use crate::raster::Raster;
+#[cfg(feature = "ndarray")]
+use crate::raster::{PlanarConfiguration, RasterArray, Sample};
 use crate::ReadRange;
+#[cfg(feature = "ndarray")]
+use ndarray::Array3;
 
 pub struct SyncRender<'c, 'r, R> {
     config: RenderBuilder<'c>,
@@ -22,9 +26,11 @@ where
         let Self { config, reader } = self;
 
         let dimensions = config.resolution;
-        match config.region {
+        let nodata = config.nodata;
+        let raster = match config.region {
             RenderRegion::InputCrop(crop) => {
                 let level = util::render_level_from_crop(config.cog, &crop, &dimensions);
+                let level = &util::select_bands(level, &config.bands)?;
                 let indices = level.tile_indices_within_image_crop(crop);
                 let tile_cache = tiles::get_tiles(self.reader, level, indices);
                 Ok(renderer::render_image_crop_from_tile_cache(
@@ -36,6 +42,7 @@ where
             }
             RenderRegion::OutputRegion((epsg, region)) => {
                 let level = util::render_level_from_region(config.cog, epsg, &region, &dimensions)?;
+                let level = util::select_bands(&level, &config.bands)?;
                 let pixel_map = util::project_pixel_map(
                     &level,
                     &config.input_projection,
@@ -48,10 +55,32 @@ where
                 renderer::render_pixel_map(&pixel_map, &level, &tile_cache, &dimensions)
             }
             RenderRegion::Tile((x, y, z)) => {
-                let level = config.cog.get_level(z)?;
+                let level = &util::select_bands(config.cog.get_level(z)?, &config.bands)?;
                 let index = level.tile_index(y, x);
                 tiles::get_tile(reader, level, index)
             }
-        }
+        }?;
+        util::apply_nodata_render(raster, nodata)
+    }
+
+    /// Render paired with the projection of its pixels
+    pub fn render_geo(self) -> CloudTiffResult<GeoRaster> {
+        let projection = util::render_projection(&self.config)?;
+        Ok(GeoRaster::new(self.render()?, projection))
+    }
+
+    /// Render converted to an array of T, see Raster::to_array
+    #[cfg(feature = "ndarray")]
+    pub fn render_array<T: Sample>(
+        self,
+        layout: PlanarConfiguration,
+    ) -> CloudTiffResult<Array3<T>> {
+        Ok(self.render()?.to_array(layout)?)
+    }
+
+    /// Render as an array of the level's sample type
+    #[cfg(feature = "ndarray")]
+    pub fn render_raster_array(self, layout: PlanarConfiguration) -> CloudTiffResult<RasterArray> {
+        Ok(self.render()?.to_raster_array(layout)?)
     }
 }
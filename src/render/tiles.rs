@@ -7,19 +7,27 @@ use tracing::*;
 
 pub type TileCache = HashMap<usize, Raster>;
 
+// Bytes of each plane, and of the mask level's tile
+type TileBytes = (Vec<Vec<u8>>, Option<Vec<u8>>);
+
 use super::util;
 
 pub fn get_tiles<R: ReadRange>(reader: &R, level: &Level, indices: Vec<usize>) -> TileCache {
+    if let Err(e) = level.load_tile_entries(reader, &indices) {
+        warn!("Failed to load tile entries: {e:?}");
+        return TileCache::new();
+    }
     let tile_infos = util::tile_info_from_indices(level, indices);
 
     // Syncronous tile reading and extraction
     tile_infos
         .into_iter()
-        .filter_map(|(index, (start, end))| {
-            let n = (end - start) as usize;
-            let mut buf = vec![0; n];
-            match reader.read_range_exact(start, &mut buf) {
-                Ok(_) => match level.extract_tile_from_bytes(&buf) {
+        .filter_map(|(index, ranges)| {
+            if level.is_sparse(index) {
+                return level.sparse_tile().ok().map(|tile| (index, tile));
+            }
+            match read_tile_bytes(reader, level, index, &ranges) {
+                Ok((planes, mask)) => match level.extract_masked_tile(&planes, mask.as_deref()) {
                     Ok(tile) => Some((index, tile)),
                     Err(e) => {
                         warn!("Failed to extract tile: {e:?}");
@@ -36,14 +44,39 @@ pub fn get_tiles<R: ReadRange>(reader: &R, level: &Level, indices: Vec<usize>) -
 }
 
 pub fn get_tile<R: ReadRange>(reader: &R, level: &Level, index: usize) -> CloudTiffResult<Raster> {
-    let (start, end) = level.tile_byte_range(index)?;
-    let n = (end - start) as usize;
-    let mut buf = vec![0; n];
-    reader.read_range_exact(start, &mut buf)?;
-    let tile = level.extract_tile_from_bytes(&buf)?;
+    level.load_tile_entries(reader, &[index])?;
+    if level.is_sparse(index) {
+        return level.sparse_tile();
+    }
+    let ranges = level.tile_byte_ranges(index)?;
+    let (planes, mask) = read_tile_bytes(reader, level, index, &ranges)?;
+    let tile = level.extract_masked_tile(&planes, mask.as_deref())?;
     Ok(tile)
 }
 
+// Tiles have one byte range per plane, plus one for the mask level's tile
+fn read_tile_bytes<R: ReadRange>(
+    reader: &R,
+    level: &Level,
+    index: usize,
+    ranges: &[(u64, u64)],
+) -> CloudTiffResult<TileBytes> {
+    let read = |(start, end): &(u64, u64)| {
+        let n = (end - start) as usize;
+        let mut buf = vec![0; n];
+        if n > 0 {
+            reader.read_range_exact(*start, &mut buf)?;
+        }
+        Ok::<_, std::io::Error>(buf)
+    };
+    let planes = ranges.iter().map(read).collect::<Result<_, _>>()?;
+    let mask = match &level.mask {
+        Some(mask) => Some(read(&mask.tile_byte_range(index)?)?),
+        None => None,
+    };
+    Ok((planes, mask))
+}
+
 #[cfg(feature = "async")]
 pub use not_sync::*;
 #[cfg(feature = "async")]
@@ -58,6 +91,16 @@ mod not_sync {
         level: &Level,
         indices: Vec<usize>,
     ) -> TileCache {
+        if let Err(e) = level
+            .load_tile_entries_async(reader.as_ref(), &indices)
+            .await
+        {
+            warn!("Failed to load tile entries: {e:?}");
+            return TileCache::new();
+        }
+        let (sparse, indices): (Vec<usize>, Vec<usize>) = indices
+            .into_iter()
+            .partition(|index| level.is_sparse(*index));
         let tile_infos = util::tile_info_from_indices(level, indices);
 
         // Async tile reading (IO)
@@ -65,14 +108,12 @@ mod not_sync {
             tile_infos
                 .into_iter()
                 .map(|info| (info, reader.clone()))
-                .map(|((index, (start, end)), reader_clone)| {
+                .map(|((index, ranges), reader_clone)| {
+                    let mask_range = level.mask.as_ref().map(|mask| mask.tile_byte_range(index));
                     tokio::spawn(async move {
-                        let n = (end - start) as usize;
-                        let mut buf = vec![0; n];
-                        reader_clone
-                            .read_range_exact_async(start, &mut buf)
+                        read_tile_bytes_async(reader_clone.as_ref(), &ranges, mask_range)
                             .await
-                            .map(|_| (index, buf))
+                            .map(|(planes, mask)| (index, planes, mask))
                     })
                 }),
         )
@@ -95,17 +136,27 @@ mod not_sync {
         //   TODO start rayon extraction without awaiting IO
         let tile_results: Vec<_> = byte_results
             .into_iter()
-            .map(|(index, bytes)| (level.clone(), index, bytes))
+            .map(|(index, planes, mask)| (level.clone(), index, planes, mask))
             .collect::<Vec<_>>()
             .into_par_iter()
-            .map(|(level_clone, index, bytes)| {
+            .map(|(level_clone, index, planes, mask)| {
                 level_clone
-                    .extract_tile_from_bytes(&bytes)
+                    .extract_masked_tile(&planes, mask.as_deref())
                     .map(|tile| (index, tile))
             })
             .collect();
 
         let mut tile_cache: HashMap<usize, Raster> = HashMap::new(); // TODO stream rather than cache
+
+        // Sparse tiles need no IO
+        for index in sparse {
+            match level.sparse_tile() {
+                Ok(tile) => {
+                    tile_cache.insert(index, tile);
+                }
+                Err(e) => warn!("Failed to create sparse tile: {e:?}"),
+            }
+        }
         for result in tile_results {
             match result {
                 Ok((index, tile)) => {
@@ -125,11 +176,44 @@ mod not_sync {
         level: &Level,
         index: usize,
     ) -> CloudTiffResult<Raster> {
-        let (start, end) = level.tile_byte_range(index)?;
+        level
+            .load_tile_entries_async(reader.as_ref(), &[index])
+            .await?;
+        if level.is_sparse(index) {
+            return level.sparse_tile();
+        }
+        let ranges = level.tile_byte_ranges(index)?;
+        let mask_range = level.mask.as_ref().map(|mask| mask.tile_byte_range(index));
+        let (planes, mask) = read_tile_bytes_async(reader.as_ref(), &ranges, mask_range).await?;
+        let tile = level.extract_masked_tile(&planes, mask.as_deref())?;
+        Ok(tile)
+    }
+
+    async fn read_tile_bytes_async<R: AsyncReadRange>(
+        reader: &R,
+        ranges: &[(u64, u64)],
+        mask_range: Option<CloudTiffResult<(u64, u64)>>,
+    ) -> CloudTiffResult<TileBytes> {
+        let mut planes = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            planes.push(read_range_async(reader, range).await?);
+        }
+        let mask = match mask_range {
+            Some(range) => Some(read_range_async(reader, &range?).await?),
+            None => None,
+        };
+        Ok((planes, mask))
+    }
+
+    async fn read_range_async<R: AsyncReadRange>(
+        reader: &R,
+        (start, end): &(u64, u64),
+    ) -> std::io::Result<Vec<u8>> {
         let n = (end - start) as usize;
         let mut buf = vec![0; n];
-        reader.read_range_exact_async(start, &mut buf).await?;
-        let tile = level.extract_tile_from_bytes(&buf)?;
-        Ok(tile)
+        if n > 0 {
+            reader.read_range_exact_async(*start, &mut buf).await?;
+        }
+        Ok(buf)
     }
 }
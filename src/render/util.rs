@@ -1,8 +1,11 @@
+use super::{NoDataRender, RenderBuilder, RenderRegion};
 use crate::cog::{CloudTiff, CloudTiffResult, Level};
 use crate::projection::{Projection, ProjectionError};
+use crate::raster::Raster;
 use crate::CloudTiffError;
 use crate::{Region, UnitFloat};
 use proj4rs::Proj;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use tracing::*;
 
@@ -66,10 +69,21 @@ pub fn render_level_from_region(
     cog.get_level(level_index).cloned()
 }
 
-pub fn tile_info_from_indices(level: &Level, indices: Vec<usize>) -> Vec<(usize, (u64, u64))> {
+// Borrows the level unless bands are selected
+pub fn select_bands<'a>(
+    level: &'a Level,
+    bands: &Option<Vec<usize>>,
+) -> CloudTiffResult<Cow<'a, Level>> {
+    match bands {
+        Some(bands) => Ok(Cow::Owned(level.select_bands(bands)?)),
+        None => Ok(Cow::Borrowed(level)),
+    }
+}
+
+pub fn tile_info_from_indices(level: &Level, indices: Vec<usize>) -> Vec<(usize, Vec<(u64, u64)>)> {
     indices
         .into_iter()
-        .filter_map(|index| match level.tile_byte_range(index) {
+        .filter_map(|index| match level.tile_byte_ranges(index) {
             Ok(range) => Some((index, range)),
             Err(e) => {
                 warn!("Failed to get tile byte range: {e:?}");
@@ -122,3 +136,44 @@ pub fn project_pixel_map(
         Ok(pixel_map)
     }
 }
+
+pub fn apply_nodata_render(raster: Raster, nodata: NoDataRender) -> CloudTiffResult<Raster> {
+    match nodata {
+        NoDataRender::Untouched => Ok(raster),
+        NoDataRender::Alpha => Ok(raster.with_alpha_from_mask()?),
+    }
+}
+
+// Georeference of a render's output, the render covers the whole unit square
+pub fn render_projection(config: &RenderBuilder) -> CloudTiffResult<Projection> {
+    let input = &config.cog.projection;
+    let input_crop = |u: f64, v: f64, du: f64, dv: f64| Projection {
+        epsg: input.epsg,
+        proj: input.proj.clone(),
+        origin: (
+            input.origin.0 + u * input.scale.0,
+            input.origin.1 - v * input.scale.1,
+            input.origin.2,
+        ),
+        scale: (input.scale.0 * du, input.scale.1 * dv, input.scale.2),
+    };
+    Ok(match &config.region {
+        RenderRegion::InputCrop(crop) => {
+            let (left, top, right, bottom) = crop.to_f64();
+            input_crop(left, top, right - left, bottom - top)
+        }
+        RenderRegion::OutputRegion((epsg, region)) => Projection {
+            epsg: *epsg,
+            proj: Proj::from_epsg_code(*epsg).map_err(ProjectionError::from)?,
+            origin: (region.x.min, region.y.max, 0.0),
+            scale: (region.x.range(), region.y.range(), 0.0),
+        },
+        RenderRegion::Tile((x, y, z)) => {
+            // Edge tiles extend past the image
+            let level = config.cog.get_level(*z)?;
+            let du = level.tile_width as f64 / level.dimensions.0 as f64;
+            let dv = level.tile_height as f64 / level.dimensions.1 as f64;
+            input_crop(*x as f64 * du, *y as f64 * dv, du, dv)
+        }
+    })
+}
@@ -10,6 +10,37 @@ pub enum Endian {
 }
 
 impl Endian {
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    /// Byte swap multi-byte samples between this and the native endianness.
+    /// Buffers with samples that are not byte aligned are left as is.
+    pub fn swap_samples(&self, buffer: &mut [u8], bits_per_sample: &[u16]) {
+        if *self == Self::native()
+            || bits_per_sample.iter().all(|bits| *bits <= 8)
+            || bits_per_sample.iter().any(|bits| bits % 8 != 0)
+        {
+            return;
+        }
+        let sizes: Vec<usize> = bits_per_sample
+            .iter()
+            .map(|bits| *bits as usize / 8)
+            .collect();
+        let mut start = 0;
+        for size in sizes.iter().cycle() {
+            let Some(sample) = buffer.get_mut(start..start + size) else {
+                break;
+            };
+            sample.reverse();
+            start += size;
+        }
+    }
+
     pub fn read<const N: usize, T: FromBytes<N>>(&self, stream: &mut impl Read) -> Result<T> {
         let mut buf = [0u8; N];
         stream.read_exact(&mut buf)?;
@@ -10,6 +10,13 @@ pub enum TiffError {
     ReadError(io::Error),
     MissingTag(TagId),
     BadTag(TagId),
+    IfdLoop(u64),                // offset of an IFD that was already parsed
+    TooManyIfds(usize),          // limit
+    TooManyTags((u64, usize)),   // (tag count, limit)
+    TagTooLarge((u16, u64)),     // (tag code, payload bytes)
+    HeaderTooLarge(u64),         // limit on the payload of all tags
+    TileTooLarge(usize),         // limit on decompressed bytes
+    CompressedTileTooLarge(u64), // stored bytes of the tile
 }
 
 impl From<io::Error> for TiffError {
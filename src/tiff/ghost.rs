@@ -0,0 +1,119 @@
+// GDAL COG structural metadata, the "ghost area" right after the TIFF header
+//   GDAL_STRUCTURAL_METADATA_SIZE=000140 bytes
+//   LAYOUT=IFDS_BEFORE_DATA
+//   BLOCK_ORDER=ROW_MAJOR
+//   BLOCK_LEADER=SIZE_AS_UINT4
+//   BLOCK_TRAILER=LAST_4_BYTES_REPEATED
+//   KNOWN_INCOMPATIBLE_EDITION=NO
+//   MASK_INTERLEAVED_WITH_IMAGERY=YES
+//   The size counts the bytes after the first line. Its items describe layout guarantees that hold
+//   until a non COG aware writer modifies the file, which GDAL flags with KNOWN_INCOMPATIBLE_EDITION.
+//   https://gdal.org/en/stable/drivers/raster/cog.html#header-ghost-area
+
+use std::fmt::Display;
+
+const PREFIX: &[u8] = b"GDAL_STRUCTURAL_METADATA_SIZE=";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GhostArea {
+    pub items: Vec<(String, String)>,
+}
+
+impl GhostArea {
+    // First line, "GDAL_STRUCTURAL_METADATA_SIZE=XXXXXX bytes\n"
+    pub(crate) const FIRST_LINE_SIZE: u64 = 43;
+
+    /// Layout of the files written by Encoder
+    pub fn cog() -> Self {
+        Self {
+            items: vec![
+                ("LAYOUT".into(), "IFDS_BEFORE_DATA".into()),
+                ("BLOCK_ORDER".into(), "ROW_MAJOR".into()),
+                ("KNOWN_INCOMPATIBLE_EDITION".into(), "NO".into()),
+            ],
+        }
+    }
+
+    /// Size of the items following the first line, None if this isn't a ghost area
+    pub fn parse_size(first_line: &[u8]) -> Option<u64> {
+        let size = first_line.strip_prefix(PREFIX)?.strip_suffix(b" bytes\n")?;
+        std::str::from_utf8(size).ok()?.parse().ok()
+    }
+
+    /// Parse the items following the first line, trailing padding is ignored
+    pub fn parse(items: &[u8]) -> Self {
+        let items = String::from_utf8_lossy(items)
+            .lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Self { items }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// IFDs and their tag data all come before any tile data
+    pub fn ifds_before_data(&self) -> bool {
+        self.get("LAYOUT") == Some("IFDS_BEFORE_DATA") && !self.known_incompatible_edition()
+    }
+
+    /// Tiles of each level are stored in row major order
+    pub fn row_major_blocks(&self) -> bool {
+        self.get("BLOCK_ORDER") == Some("ROW_MAJOR") && !self.known_incompatible_edition()
+    }
+
+    /// Tile data is preceded by its byte count as a u32
+    pub fn block_leader_size(&self) -> bool {
+        self.get("BLOCK_LEADER") == Some("SIZE_AS_UINT4") && !self.known_incompatible_edition()
+    }
+
+    /// Tile data is followed by a copy of its last 4 bytes, to detect partial writes
+    pub fn block_trailer_repeated(&self) -> bool {
+        self.get("BLOCK_TRAILER") == Some("LAST_4_BYTES_REPEATED")
+            && !self.known_incompatible_edition()
+    }
+
+    /// Mask tiles directly follow the image tile they cover
+    pub fn mask_interleaved(&self) -> bool {
+        self.get("MASK_INTERLEAVED_WITH_IMAGERY") == Some("YES")
+            && !self.known_incompatible_edition()
+    }
+
+    /// The file was modified after being written as a COG, none of the layout items hold
+    pub fn known_incompatible_edition(&self) -> bool {
+        self.get("KNOWN_INCOMPATIBLE_EDITION") == Some("YES")
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut items: String = self
+            .items
+            .iter()
+            .map(|(key, value)| format!("{key}={value}\n"))
+            .collect();
+        // Trailing space so the IFD that follows starts on a word boundary (as GDAL)
+        if !(Self::FIRST_LINE_SIZE + items.len() as u64).is_multiple_of(2) {
+            items.push(' ');
+        }
+        let mut bytes = format!("GDAL_STRUCTURAL_METADATA_SIZE={:06} bytes\n", items.len());
+        bytes.push_str(&items);
+        bytes.into_bytes()
+    }
+}
+
+impl Display for GhostArea {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GhostArea: {{")?;
+        for (i, (key, value)) in self.items.iter().enumerate() {
+            match i {
+                0 => write!(f, "{key}={value}")?,
+                _ => write!(f, ", {key}={value}")?,
+            }
+        }
+        write!(f, "}}")
+    }
+}
@@ -1,69 +1,15 @@
 use num_traits::NumCast;
 
-use super::{Endian, Tag, TagData, TagId, TagType, TiffError, TiffOffsets, TiffVariant};
+use super::{Endian, Tag, TagData, TagId, TiffError, TiffOffsets, TiffVariant};
 use std::{
     collections::HashMap,
-    io::{self, Read, Seek, SeekFrom, Write},
+    io::{self, Seek, Write},
 };
 
 #[derive(Clone, Debug, Default)]
 pub struct Ifd(pub Vec<Tag>);
 
 impl Ifd {
-    pub fn parse<R: Read + Seek>(
-        stream: &mut R,
-        offset: u64,
-        endian: Endian,
-        variant: TiffVariant,
-    ) -> io::Result<(Ifd, u64)> {
-        // IFD starts at offset
-        stream.seek(SeekFrom::Start(offset))?;
-
-        // IFD header is just the number of tags
-        let tag_count = match variant {
-            TiffVariant::Normal => endian.read::<2, u16>(stream)? as u64,
-            TiffVariant::Big => endian.read(stream)?,
-        };
-
-        // Parse each tag in the IFD
-        let mut tags = Vec::with_capacity(tag_count as usize);
-        for _ in 0..tag_count {
-            let code = endian.read(stream)?;
-            let datatype: TagType = endian.read::<2, u16>(stream)?.into();
-            let count = variant.read_offset(endian, stream)? as usize;
-
-            let data_size = count * datatype.size_in_bytes();
-            let offset_size = variant.offset_bytesize();
-            let mut data: Vec<u8> = vec![0; data_size.max(offset_size)];
-
-            if data_size > offset_size {
-                let data_offset = variant.read_offset(endian, stream)?;
-                let pos = stream.stream_position()?;
-                stream.seek(SeekFrom::Start(data_offset))?;
-                stream.read_exact(&mut data)?;
-                stream.seek(SeekFrom::Start(pos))?;
-            } else {
-                stream.read_exact(&mut data)?;
-                if data_size < offset_size {
-                    data = data[0..data_size].to_vec();
-                }
-            }
-
-            tags.push(Tag {
-                code,
-                datatype,
-                endian,
-                count,
-                data,
-            });
-        }
-
-        let ifd = Ifd(tags);
-        let next_ifd_offset = variant.read_offset(endian, stream)?;
-
-        Ok((ifd, next_ifd_offset))
-    }
-
     pub fn get_tag_by_code(&self, code: u16) -> Option<&Tag> {
         let Self(tags) = &self;
         tags.iter().find(|tag| tag.code == code)
@@ -137,13 +83,16 @@ impl Ifd {
                 let data_offset = extra_data_offset + extra_data.len() as u64;
                 variant.write_offset(endian, stream, data_offset)?;
                 extra_data.extend_from_slice(&tag.data);
+                if !extra_data.len().is_multiple_of(2) {
+                    extra_data.push(0); // Values start on a word boundary
+                }
                 data_offset
             } else {
                 let bytes: Vec<u8> = tag
                     .data
                     .clone()
                     .into_iter()
-                    .chain(vec![0; offset_size].into_iter())
+                    .chain(vec![0; offset_size])
                     .take(offset_size)
                     .collect();
                 let data_offset = stream.stream_position()?;
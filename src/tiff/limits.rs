@@ -0,0 +1,70 @@
+// Resource limits for parsing untrusted files
+//   Counts and sizes in a TIFF are read from the file, without limits a crafted file can request
+//   huge allocations. Each exceeded limit is reported as its own TiffError.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TiffLimits {
+    pub max_ifds: usize,
+    pub max_tags_per_ifd: usize,
+    pub max_tag_bytes: u64, // payload of a single tag, deferred tags are not read whole
+    pub max_header_bytes: u64, // payload of all tags of all IFDs together
+    pub max_tile_bytes: usize, // decompressed size of a single tile (or plane)
+    pub max_compressed_tile_bytes: u64, // stored size of a single tile (or plane)
+}
+
+impl Default for TiffLimits {
+    fn default() -> Self {
+        Self {
+            max_ifds: 1024,
+            max_tags_per_ifd: 1024,
+            max_tag_bytes: 64 * 1024 * 1024,
+            max_header_bytes: 256 * 1024 * 1024,
+            max_tile_bytes: 256 * 1024 * 1024,
+            max_compressed_tile_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl TiffLimits {
+    /// No limits, for trusted files
+    pub fn unlimited() -> Self {
+        Self {
+            max_ifds: usize::MAX,
+            max_tags_per_ifd: usize::MAX,
+            max_tag_bytes: u64::MAX,
+            max_header_bytes: u64::MAX,
+            max_tile_bytes: usize::MAX,
+            max_compressed_tile_bytes: u64::MAX,
+        }
+    }
+
+    pub fn with_max_ifds(mut self, max: usize) -> Self {
+        self.max_ifds = max;
+        self
+    }
+
+    pub fn with_max_tags_per_ifd(mut self, max: usize) -> Self {
+        self.max_tags_per_ifd = max;
+        self
+    }
+
+    pub fn with_max_tag_bytes(mut self, max: u64) -> Self {
+        self.max_tag_bytes = max;
+        self
+    }
+
+    pub fn with_max_header_bytes(mut self, max: u64) -> Self {
+        self.max_header_bytes = max;
+        self
+    }
+
+    pub fn with_max_tile_bytes(mut self, max: usize) -> Self {
+        self.max_tile_bytes = max;
+        self
+    }
+
+    pub fn with_max_compressed_tile_bytes(mut self, max: u64) -> Self {
+        self.max_compressed_tile_bytes = max;
+        self
+    }
+}
@@ -1,15 +1,21 @@
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::io::{self, Read, Seek, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 mod endian;
 mod error;
+mod ghost;
 mod ifd;
+mod limits;
+mod parser;
 mod tag;
 
 pub use endian::Endian;
 pub use error::TiffError;
+pub use ghost::GhostArea;
 pub use ifd::Ifd;
+pub use limits::TiffLimits;
+pub use parser::{ParseStep, TiffParser};
 pub use tag::{Tag, TagData, TagId, TagType};
 
 #[derive(PartialEq, Clone, Copy, Debug)]
@@ -19,12 +25,6 @@ pub enum TiffVariant {
 }
 
 impl TiffVariant {
-    fn read_offset<R: Read>(&self, endian: Endian, stream: &mut R) -> io::Result<u64> {
-        match self {
-            TiffVariant::Normal => endian.read::<4, u32>(stream).map(|v| v as u64),
-            TiffVariant::Big => endian.read(stream),
-        }
-    }
     fn write_offset<W: Write>(
         &self,
         endian: Endian,
@@ -51,6 +51,8 @@ pub struct Tiff {
     pub endian: Endian,
     pub variant: TiffVariant,
     pub ifds: Vec<Ifd>,
+    pub ghost_area: Option<GhostArea>, // GDAL COG structural metadata
+    pub limits: TiffLimits,            // used to parse the file, and to decode its tiles
 }
 
 impl Tiff {
@@ -59,46 +61,33 @@ impl Tiff {
             endian,
             variant,
             ifds: vec![Ifd::default()],
+            ghost_area: None,
+            limits: TiffLimits::default(),
         }
     }
 
     pub fn open<R: Read + Seek>(stream: &mut R) -> Result<Self, TiffError> {
-        // TIFF Header
-        let mut buf = [0; 4];
-        stream.read_exact(&mut buf)?;
-
-        let endian = match &buf[..2] {
-            b"II" => Endian::Little,
-            b"MM" => Endian::Big,
-            _ => return Err(TiffError::BadMagicBytes),
-        };
-
-        let variant = match &buf[2..4] {
-            b"\0*" | b"*\0" => TiffVariant::Normal,
-            b"\0+" | b"+\0" => TiffVariant::Big,
-            _ => return Err(TiffError::BadMagicBytes),
-        };
-
-        if TiffVariant::Big == variant {
-            // BigTIFFs have 4 extra bytes in the header
-            let _offset_bytesize: u16 = endian.read(stream)?; // 0x0008
-            let _: u16 = endian.read(stream)?; // 0x0000
-        }
+        Self::open_with_limits(stream, TiffLimits::default())
+    }
 
-        // IFDs
-        let mut ifds = vec![];
-        let mut ifd_offset = variant.read_offset(endian, stream)?;
-        while ifd_offset != 0 {
-            let (ifd, next_offset) = Ifd::parse(stream, ifd_offset, endian, variant)?;
-            ifd_offset = next_offset;
-            ifds.push(ifd);
+    pub fn open_with_limits<R: Read + Seek>(
+        stream: &mut R,
+        limits: TiffLimits,
+    ) -> Result<Self, TiffError> {
+        let mut parser = TiffParser::new().with_limits(limits);
+        loop {
+            match parser.parse()? {
+                ParseStep::NeedBytes(ranges) => {
+                    for range in ranges {
+                        let mut bytes = vec![0; (range.end - range.start) as usize];
+                        stream.seek(SeekFrom::Start(range.start))?;
+                        stream.read_exact(&mut bytes)?;
+                        parser.provide(range.start, &bytes);
+                    }
+                }
+                ParseStep::Done(tiff) => return Ok(tiff),
+            }
         }
-
-        Ok(Self {
-            endian,
-            variant,
-            ifds,
-        })
     }
 
     pub fn ifd0(&self) -> Result<&Ifd, TiffError> {
@@ -129,12 +118,18 @@ impl Tiff {
             endian.write(stream, 0x0000_u16)?;
         }
 
-        // IFD0 offset
+        // IFD0 offset, after the ghost area if any
+        let ghost_area = self
+            .ghost_area
+            .as_ref()
+            .map(|ghost| ghost.to_bytes())
+            .unwrap_or_default();
         if self.variant == TiffVariant::Big {
-            endian.write(stream, 16_u64)?;
+            endian.write(stream, 16 + ghost_area.len() as u64)?;
         } else {
-            endian.write(stream, 8_u32)?;
+            endian.write(stream, 8 + ghost_area.len() as u32)?;
         }
+        stream.write_all(&ghost_area)?;
 
         // IFDs
         let mut offsets = vec![];
@@ -154,6 +149,9 @@ impl Display for Tiff {
             "Tiff: {{{:?} Endian, {:?} Variant}}",
             self.endian, self.variant
         )?;
+        if let Some(ghost_area) = &self.ghost_area {
+            write!(f, "\n  {ghost_area}")?;
+        }
         for (i, ifd) in self.ifds.iter().enumerate() {
             write!(f, "\n  IFD {i}:")?;
             for tag in ifd.0.iter() {
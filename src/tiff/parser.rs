@@ -0,0 +1,424 @@
+// Sans-IO TIFF parsing
+//   TiffParser never reads, it asks for the byte ranges it needs and parses whatever it is given.
+//   Drive it by providing the requested ranges until it is done:
+//     let mut parser = TiffParser::new();
+//     let tiff = loop {
+//         match parser.parse()? {
+//             ParseStep::NeedBytes(ranges) => {
+//                 for range in ranges {
+//                     parser.provide(range.start, &read(range));
+//                 }
+//             }
+//             ParseStep::Done(tiff) => break tiff,
+//         }
+//     };
+//   All requested ranges are independent, so they can be fetched concurrently.
+//   Bytes beyond what was requested (e.g. read ahead) are kept and save later round trips.
+//   Large tag arrays can be deferred with with_deferred_tags, they are left unread and their Tag
+//   only holds the file offset of the data (see Tag::deferred).
+//   GDAL COGs declare their layout in a ghost area after the header, when all IFDs come before the
+//   tile data header_hint estimates their byte range so they can be read in one request.
+//   Counts and sizes are checked against TiffLimits before anything is requested or allocated,
+//   and IFD chains that loop back are an error.
+
+use super::{
+    Endian, GhostArea, Ifd, Tag, TagId, TagType, Tiff, TiffError, TiffLimits, TiffVariant,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::mem;
+use std::ops::Range;
+
+#[derive(Debug)]
+pub enum ParseStep {
+    NeedBytes(Vec<Range<u64>>),
+    Done(Tiff),
+}
+
+#[derive(Debug, Default)]
+pub struct TiffParser {
+    header: Option<(Endian, TiffVariant)>,
+    state: State,
+    ifds: Vec<Ifd>,
+    chunks: BTreeMap<u64, Vec<u8>>, // provided bytes by offset, never overlapping
+    deferred: Vec<u16>,
+    deferred_min_bytes: u64,
+    ghost_area: Option<GhostArea>,
+    header_hint: Option<Range<u64>>,
+    limits: TiffLimits,
+    visited: HashSet<u64>, // IFD offsets
+    header_bytes: u64,     // out of line tag data of all IFDs so far
+}
+
+// Next state, with the missing byte ranges if the state could not progress
+type Progress = (State, Option<Vec<Range<u64>>>);
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Header,
+    GhostArea(u64), // first ifd offset
+    IfdCount(u64),
+    IfdEntries((u64, u64)),     // (offset of the entries, entry count)
+    TagData((Vec<Entry>, u64)), // (entries, next ifd offset)
+    Done,
+}
+
+#[derive(Debug)]
+struct Entry {
+    code: u16,
+    datatype: TagType,
+    count: usize,
+    data: EntryData,
+}
+
+#[derive(Debug)]
+enum EntryData {
+    Inline(Vec<u8>),
+    Offset(Range<u64>),
+    Deferred(Range<u64>),
+}
+
+impl TiffParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave the data of these tags unread when it is at least min_bytes long
+    pub fn with_deferred_tags(mut self, tags: &[TagId], min_bytes: u64) -> Self {
+        self.deferred = tags.iter().map(|id| *id as u16).collect();
+        self.deferred_min_bytes = min_bytes;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: TiffLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn ghost_area(&self) -> Option<&GhostArea> {
+        self.ghost_area.as_ref()
+    }
+
+    /// Estimated byte range of every IFD and its tag data, for reading them in one request
+    ///   Known once the first IFD is found, if the ghost area declares LAYOUT=IFDS_BEFORE_DATA.
+    ///   Deferred tag data is left out, and the range starts after the bytes already provided.
+    ///   The full resolution IFD is the largest, overviews add at most a third of its tiles and an
+    ///   internal mask doubles them, so three times its size covers all of them.
+    pub fn header_hint(&self) -> Option<Range<u64>> {
+        self.header_hint.clone()
+    }
+
+    /// Bytes read from the source starting at offset, any range is accepted
+    pub fn provide(&mut self, offset: u64, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let end = offset + bytes.len() as u64;
+
+        // Merge with any overlapping or adjacent chunks
+        let touching: Vec<u64> = self
+            .chunks
+            .range(..=end)
+            .filter(|(start, chunk)| **start + chunk.len() as u64 >= offset)
+            .map(|(start, _)| *start)
+            .collect();
+        let mut start = offset;
+        let mut merged_end = end;
+        let mut old = vec![];
+        for key in touching {
+            let chunk = self.chunks.remove(&key).unwrap_or_default();
+            start = start.min(key);
+            merged_end = merged_end.max(key + chunk.len() as u64);
+            old.push((key, chunk));
+        }
+        let mut merged = vec![0; (merged_end - start) as usize];
+        for (key, chunk) in old {
+            let i = (key - start) as usize;
+            merged[i..i + chunk.len()].copy_from_slice(&chunk);
+        }
+        let i = (offset - start) as usize;
+        merged[i..i + bytes.len()].copy_from_slice(bytes);
+        self.chunks.insert(start, merged);
+    }
+
+    /// Parse as far as the provided bytes allow
+    ///   The parsed Tiff is moved out when done, so the parser is spent afterwards.
+    pub fn parse(&mut self) -> Result<ParseStep, TiffError> {
+        loop {
+            let state = mem::take(&mut self.state);
+            match self.advance(state)? {
+                (State::Done, _) => {
+                    let (endian, variant) = self.header.ok_or(TiffError::BadMagicBytes)?;
+                    return Ok(ParseStep::Done(Tiff {
+                        endian,
+                        variant,
+                        ifds: mem::take(&mut self.ifds),
+                        ghost_area: self.ghost_area.take(),
+                        limits: self.limits,
+                    }));
+                }
+                (state, Some(ranges)) => {
+                    self.state = state;
+                    return Ok(ParseStep::NeedBytes(ranges));
+                }
+                (state, None) => self.state = state,
+            }
+        }
+    }
+
+    fn advance(&mut self, state: State) -> Result<Progress, TiffError> {
+        let need = |state, range| Ok((state, Some(vec![range])));
+        match state {
+            State::Header => {
+                let Some(bytes) = self.bytes(0..8) else {
+                    return need(State::Header, 0..8);
+                };
+                let endian = match &bytes[..2] {
+                    b"II" => Endian::Little,
+                    b"MM" => Endian::Big,
+                    _ => return Err(TiffError::BadMagicBytes),
+                };
+                let variant = match &bytes[2..4] {
+                    b"\0*" | b"*\0" => TiffVariant::Normal,
+                    b"\0+" | b"+\0" => TiffVariant::Big,
+                    _ => return Err(TiffError::BadMagicBytes),
+                };
+                let offset = match variant {
+                    TiffVariant::Normal => uint(endian, &bytes[4..8]),
+                    // BigTIFFs have 8 more bytes in the header, the offset bytesize and 0
+                    TiffVariant::Big => match self.bytes(8..16) {
+                        Some(bytes) => uint(endian, bytes),
+                        None => return need(State::Header, 8..16),
+                    },
+                };
+                self.header = Some((endian, variant));
+                Ok((State::GhostArea(offset), None))
+            }
+            State::GhostArea(first_ifd) => {
+                // Between the header and the first IFD, if there is room for it
+                let (_, variant) = self.header()?;
+                let start = match variant {
+                    TiffVariant::Normal => 8,
+                    TiffVariant::Big => 16,
+                };
+                let line = start..start + GhostArea::FIRST_LINE_SIZE;
+                if first_ifd < line.end {
+                    return Ok((self.next_ifd(first_ifd)?, None));
+                }
+                let Some(bytes) = self.bytes(line.clone()) else {
+                    return need(State::GhostArea(first_ifd), line);
+                };
+                let items = match GhostArea::parse_size(bytes) {
+                    Some(size) if line.end + size <= first_ifd => line.end..line.end + size,
+                    _ => return Ok((self.next_ifd(first_ifd)?, None)),
+                };
+                let Some(bytes) = self.bytes(items.clone()) else {
+                    return need(State::GhostArea(first_ifd), items);
+                };
+                self.ghost_area = Some(GhostArea::parse(bytes));
+                Ok((self.next_ifd(first_ifd)?, None))
+            }
+            State::IfdCount(offset) => {
+                let (endian, variant) = self.header()?;
+                let size = match variant {
+                    TiffVariant::Normal => 2,
+                    TiffVariant::Big => 8,
+                };
+                let range = offset..offset.saturating_add(size);
+                match self.bytes(range.clone()) {
+                    Some(bytes) => {
+                        let count = uint(endian, bytes);
+                        Ok((State::IfdEntries((range.end, count)), None))
+                    }
+                    None => need(State::IfdCount(offset), range),
+                }
+            }
+            State::IfdEntries((offset, count)) => {
+                let (endian, variant) = self.header()?;
+                let offset_size = variant.offset_bytesize();
+                let entry_size = 4 + 2 * offset_size as u64;
+                if count > self.limits.max_tags_per_ifd as u64 {
+                    return Err(TiffError::TooManyTags((
+                        count,
+                        self.limits.max_tags_per_ifd,
+                    )));
+                }
+                let length = count
+                    .checked_mul(entry_size)
+                    .and_then(|length| length.checked_add(offset_size as u64))
+                    .ok_or_else(|| invalid(format!("IFD at {offset} has {count} entries")))?;
+                let range = offset..offset.saturating_add(length);
+                let Some(bytes) = self.bytes(range.clone()) else {
+                    return need(State::IfdEntries((offset, count)), range);
+                };
+
+                let mut entries = Vec::with_capacity(count as usize);
+                let mut header_bytes = self.header_bytes;
+                for entry in bytes.chunks_exact(entry_size as usize) {
+                    let datatype: TagType = (uint(endian, &entry[2..4]) as u16).into();
+                    let count = uint(endian, &entry[4..4 + offset_size]) as usize;
+                    let value = &entry[4 + offset_size..];
+                    let data_size = count
+                        .checked_mul(datatype.size_in_bytes())
+                        .ok_or_else(|| invalid(format!("Tag with {count} values")))?;
+                    let code = uint(endian, &entry[..2]) as u16;
+                    let data = match data_size > offset_size {
+                        true => {
+                            let data_offset = uint(endian, value);
+                            let range = data_offset..data_offset.saturating_add(data_size as u64);
+                            match self.deferred.contains(&code)
+                                && data_size as u64 >= self.deferred_min_bytes
+                            {
+                                true => EntryData::Deferred(range),
+                                false if data_size as u64 > self.limits.max_tag_bytes => {
+                                    return Err(TiffError::TagTooLarge((code, data_size as u64)));
+                                }
+                                false => {
+                                    header_bytes = header_bytes.saturating_add(data_size as u64);
+                                    if header_bytes > self.limits.max_header_bytes {
+                                        return Err(TiffError::HeaderTooLarge(
+                                            self.limits.max_header_bytes,
+                                        ));
+                                    }
+                                    EntryData::Offset(range)
+                                }
+                            }
+                        }
+                        false => EntryData::Inline(value[..data_size].to_vec()),
+                    };
+                    entries.push(Entry {
+                        code,
+                        datatype,
+                        count,
+                        data,
+                    });
+                }
+                let next = uint(endian, &bytes[bytes.len() - offset_size..]);
+                self.header_bytes = header_bytes;
+
+                let ifds_before_data = self
+                    .ghost_area
+                    .as_ref()
+                    .is_some_and(|ghost| ghost.ifds_before_data());
+                if self.ifds.is_empty() && ifds_before_data {
+                    let count_size = match variant {
+                        TiffVariant::Normal => 2,
+                        TiffVariant::Big => 8,
+                    };
+                    let ifd_start = offset.saturating_sub(count_size);
+                    // Deferred tag data is never fetched
+                    let tag_data: u64 = entries
+                        .iter()
+                        .map(|entry| match &entry.data {
+                            EntryData::Offset(range) => range.end - range.start,
+                            EntryData::Inline(_) | EntryData::Deferred(_) => 0,
+                        })
+                        .sum();
+                    let size = (range.end - ifd_start).saturating_add(tag_data);
+                    let end = ifd_start.saturating_add(3 * size);
+                    // Bytes already provided are not asked for again
+                    let start = self
+                        .chunks
+                        .range(..=ifd_start)
+                        .next_back()
+                        .map(|(start, chunk)| start + chunk.len() as u64)
+                        .map_or(ifd_start, |held| held.max(ifd_start));
+                    self.header_hint = (start < end).then_some(start..end);
+                }
+                Ok((State::TagData((entries, next)), None))
+            }
+            State::TagData((entries, next)) => {
+                let missing: Vec<Range<u64>> = entries
+                    .iter()
+                    .filter_map(|entry| match &entry.data {
+                        EntryData::Offset(range) if self.bytes(range.clone()).is_none() => {
+                            Some(range.clone())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                if !missing.is_empty() {
+                    // Ask for the next IFD's count in the same round trip
+                    let (_, variant) = self.header()?;
+                    let count_range = next..next.saturating_add(variant.offset_bytesize() as u64);
+                    let mut ranges = missing;
+                    if next != 0 && self.bytes(count_range.clone()).is_none() {
+                        ranges.push(count_range);
+                    }
+                    return Ok((State::TagData((entries, next)), Some(ranges)));
+                }
+
+                let (endian, _) = self.header()?;
+                let mut tags = Vec::with_capacity(entries.len());
+                for entry in entries {
+                    let (data, deferred) = match entry.data {
+                        EntryData::Inline(data) => (data, None),
+                        EntryData::Offset(range) => {
+                            (self.bytes(range).unwrap_or_default().to_vec(), None)
+                        }
+                        // Deferred data that was provided anyway is kept
+                        EntryData::Deferred(range) => match self.bytes(range.clone()) {
+                            Some(bytes) => (bytes.to_vec(), None),
+                            None => (vec![], Some(range.start)),
+                        },
+                    };
+                    tags.push(Tag {
+                        code: entry.code,
+                        datatype: entry.datatype,
+                        endian,
+                        count: entry.count,
+                        data,
+                        deferred,
+                    });
+                }
+                self.ifds.push(Ifd(tags));
+                Ok((self.next_ifd(next)?, None))
+            }
+            State::Done => Ok((State::Done, None)),
+        }
+    }
+
+    fn next_ifd(&mut self, offset: u64) -> Result<State, TiffError> {
+        if offset == 0 {
+            return Ok(State::Done);
+        }
+        if !self.visited.insert(offset) {
+            return Err(TiffError::IfdLoop(offset));
+        }
+        if self.visited.len() > self.limits.max_ifds {
+            return Err(TiffError::TooManyIfds(self.limits.max_ifds));
+        }
+        Ok(State::IfdCount(offset))
+    }
+
+    fn header(&self) -> Result<(Endian, TiffVariant), TiffError> {
+        self.header.ok_or(TiffError::BadMagicBytes)
+    }
+
+    // Provided bytes of a range, if they were all provided
+    fn bytes(&self, range: Range<u64>) -> Option<&[u8]> {
+        let (start, chunk) = self.chunks.range(..=range.start).next_back()?;
+        let i = (range.start - start) as usize;
+        let j = (range.end - start) as usize;
+        chunk.get(i..j)
+    }
+}
+
+fn uint(endian: Endian, bytes: &[u8]) -> u64 {
+    let mut array = [0; 8];
+    match endian {
+        Endian::Big => {
+            array[8 - bytes.len()..].copy_from_slice(bytes);
+            u64::from_be_bytes(array)
+        }
+        Endian::Little => {
+            array[..bytes.len()].copy_from_slice(bytes);
+            u64::from_le_bytes(array)
+        }
+    }
+}
+
+fn invalid(message: String) -> TiffError {
+    TiffError::ReadError(io::Error::new(io::ErrorKind::InvalidData, message))
+}
@@ -40,4 +40,5 @@ pub enum TagId {
     GeoAsciiParams = 0x87B1,
     GDALMetadata = 0xA480,
     GDALNoData = 0xA481,
+    LercParameters = 0xC5F2,
 }
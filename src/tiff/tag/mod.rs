@@ -8,6 +8,7 @@ use eio::FromBytes;
 use num_enum::{FromPrimitive, IntoPrimitive};
 use num_traits::{cast::NumCast, ToPrimitive};
 use std::fmt::Display;
+use std::ops::Range;
 
 mod data;
 mod id;
@@ -22,6 +23,7 @@ pub struct Tag {
     pub count: usize,
     pub data: Vec<u8>,
     pub endian: Endian,
+    pub deferred: Option<u64>, // file offset of out of line data that was not read, see TiffParser
 }
 
 impl Tag {
@@ -32,6 +34,7 @@ impl Tag {
             count: data.len(),
             data: data.bytes(endian),
             endian,
+            deferred: None,
         }
     }
 
@@ -47,6 +50,9 @@ impl Tag {
     }
 
     pub fn values<T: NumCast>(&self) -> Option<Vec<T>> {
+        if self.deferred.is_some() {
+            return None;
+        }
         match self.datatype {
             TagType::Byte => self.decode::<1, u8, T>(),
             TagType::Ascii => self.decode::<1, u8, T>(),
@@ -68,6 +74,25 @@ impl Tag {
         }
     }
 
+    /// File byte range of a deferred tag's values from start to end
+    pub fn deferred_range(&self, start: usize, end: usize) -> Option<Range<u64>> {
+        let offset = self.deferred?;
+        let size = self.datatype.size_in_bytes() as u64;
+        let end = end.min(self.count) as u64;
+        (start as u64 <= end).then(|| offset + start as u64 * size..offset + end * size)
+    }
+
+    /// Values of a deferred tag read from its file bytes
+    pub fn deferred_values<T: NumCast>(&self, bytes: &[u8]) -> Option<Vec<T>> {
+        let tag = Self {
+            count: bytes.len() / self.datatype.size_in_bytes(),
+            data: bytes.to_vec(),
+            deferred: None,
+            ..self.clone()
+        };
+        tag.values()
+    }
+
     pub fn try_to_string(&self) -> Option<String> {
         match self.datatype {
             TagType::Ascii | TagType::Byte | TagType::Unknown => {
@@ -133,7 +158,10 @@ impl Tag {
 
 impl Display for Tag {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut value_string = self.as_string_lossy().replace("\n", "\\n");
+        let mut value_string = match self.deferred {
+            Some(offset) => format!("Deferred at {offset}"),
+            None => self.as_string_lossy().replace("\n", "\\n"),
+        };
         if value_string.len() > 100 {
             value_string = format!("{}...", &value_string[..98])
         }
@@ -0,0 +1,189 @@
+use cloudtiff::cog::DeferredTiles;
+use cloudtiff::raster::{PhotometricInterpretation, Raster};
+use cloudtiff::tiff::{Endian, Tag, TagType};
+use cloudtiff::{tiles, CloudTiff, Encoder, ReadRange, Region};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// In memory source counting its reads and the bytes read
+struct CountingReader {
+    bytes: Vec<u8>,
+    reads: AtomicUsize,
+    read_bytes: AtomicUsize,
+}
+
+impl CountingReader {
+    fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            reads: AtomicUsize::new(0),
+            read_bytes: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ReadRange for CountingReader {
+    fn read_range(&self, start: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = (start as usize).min(self.bytes.len());
+        let n = buf.len().min(self.bytes.len() - start);
+        buf[..n].copy_from_slice(&self.bytes[start..start + n]);
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.read_bytes.fetch_add(n, Ordering::SeqCst);
+        Ok(n)
+    }
+}
+
+// 2048x1024 COG of 16x16 tiles, the full resolution level has 8192 tile entries
+fn cog(big_tiff: bool) -> Vec<u8> {
+    let raster = Raster::from_samples(
+        (2048, 1024),
+        (0..2048 * 1024).map(|i| (i % 251) as u8).collect(),
+        1,
+        PhotometricInterpretation::BlackIsZero,
+    )
+    .unwrap();
+    let mut bytes = Cursor::new(vec![]);
+    Encoder::from_raster(raster)
+        .unwrap()
+        .with_tile_size(16)
+        .with_big_tiff(big_tiff)
+        .with_projection(
+            32609,
+            Region::new(500000.0, 6000000.0 - 3000.0, 503000.0, 6000000.0),
+        )
+        .encode(&mut bytes)
+        .unwrap();
+    bytes.into_inner()
+}
+
+#[test]
+fn entries_loaded_on_demand() {
+    for big_tiff in [false, true] {
+        let bytes = cog(big_tiff);
+        let eager = CloudTiff::open(&mut Cursor::new(&bytes)).unwrap();
+        let reader = CountingReader::new(bytes.clone());
+        let lazy = CloudTiff::open_from_range_reader(&reader).unwrap();
+
+        // Opening reads less than the offsets and byte counts of the full resolution level
+        let level = &lazy.levels[0];
+        let entry_bytes = level.tile_count() * 2 * if big_tiff { 8 } else { 4 };
+        assert!(reader.read_bytes.load(Ordering::SeqCst) < entry_bytes);
+        assert!(level.deferred.is_some());
+        assert!(level.offsets.is_empty());
+        assert!(level.tile_byte_range(5).is_err());
+
+        let source = Mutex::new(Cursor::new(bytes));
+        for index in [0, 5, 1000, level.tile_count() - 1] {
+            let expected = tiles::get_tile(&source, &eager.levels[0], index).unwrap();
+            let tile = tiles::get_tile(&reader, level, index).unwrap();
+            assert_eq!(tile.buffer, expected.buffer);
+        }
+        assert_eq!(
+            level.tile_byte_range(5).unwrap(),
+            eager.levels[0].tile_byte_range(5).unwrap()
+        );
+    }
+}
+
+#[test]
+fn loaded_entries_shared() {
+    let bytes = cog(false);
+    let reader = CountingReader::new(bytes);
+    let cog = CloudTiff::open_from_range_reader(&reader).unwrap();
+    let level = &cog.levels[0];
+
+    // Entries loaded through a clone, as a renderer would, are kept for the level
+    let indices: Vec<usize> = (0..300).collect();
+    level.clone().load_tile_entries(&reader, &indices).unwrap();
+    assert!(level.missing_tile_entries(&indices).is_empty());
+
+    // Only the tile itself is read
+    let reads = reader.reads.load(Ordering::SeqCst);
+    tiles::get_tile(&reader, level, 299).unwrap();
+    assert_eq!(reader.reads.load(Ordering::SeqCst) - reads, 1);
+}
+
+#[test]
+fn render_matches_eager() {
+    let bytes = cog(false);
+    let eager = CloudTiff::open(&mut Cursor::new(&bytes)).unwrap();
+    let reader = CountingReader::new(bytes.clone());
+    let lazy = CloudTiff::open_from_range_reader(&reader).unwrap();
+    let source = Mutex::new(Cursor::new(bytes));
+    let expected = eager
+        .renderer()
+        .with_exact_resolution((256, 128))
+        .with_reader(&source)
+        .render()
+        .unwrap();
+    let raster = lazy
+        .renderer()
+        .with_exact_resolution((256, 128))
+        .with_reader(&reader)
+        .render()
+        .unwrap();
+    assert_eq!(raster.buffer, expected.buffer);
+}
+
+#[test]
+fn planes_selected() {
+    // 3 planes of 2 tiles, offsets as u32 at 1000 and byte counts as u16 at 2000
+    let mut file = vec![0u8; 3000];
+    for i in 0..6 {
+        let offset = 100 * i as u32 + 7;
+        let byte_count = i as u16 + 1;
+        file[1000 + 4 * i..1004 + 4 * i].copy_from_slice(&offset.to_le_bytes());
+        file[2000 + 2 * i..2002 + 2 * i].copy_from_slice(&byte_count.to_le_bytes());
+    }
+    let tag = |code, datatype, at| Tag {
+        code,
+        datatype,
+        endian: Endian::Little,
+        count: 6,
+        data: vec![],
+        deferred: Some(at),
+    };
+    let deferred = DeferredTiles::from_tags(
+        &tag(324, TagType::Long, 1000),
+        &tag(325, TagType::Short, 2000),
+        3,
+    )
+    .unwrap();
+    let reader = CountingReader::new(file);
+    for range in deferred.missing_ranges(&[1], 2) {
+        let bytes = reader.read_range_to_vec(range.start, range.end).unwrap();
+        deferred.provide(range.start, &bytes).unwrap();
+    }
+    assert_eq!(deferred.get(1, 0, 2).unwrap(), (107, 109));
+    assert_eq!(deferred.get(1, 2, 2).unwrap(), (507, 513));
+    assert!(deferred.get(0, 0, 2).is_err());
+
+    // A band selection keeps the loaded entries of its plane
+    let selected = DeferredTiles {
+        planes: vec![2],
+        ..deferred
+    };
+    assert_eq!(selected.get(1, 0, 2).unwrap(), (507, 513));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn entries_loaded_on_demand_async() {
+    let bytes = cog(true);
+    let eager = CloudTiff::open(&mut Cursor::new(&bytes)).unwrap();
+    let reader = std::sync::Arc::new(tokio::sync::Mutex::new(Cursor::new(bytes.clone())));
+    let lazy = CloudTiff::open_from_async_range_reader(reader.as_ref())
+        .await
+        .unwrap();
+    assert!(lazy.levels[0].deferred.is_some());
+
+    let source = Mutex::new(Cursor::new(bytes));
+    let expected = tiles::get_tile(&source, &eager.levels[0], 777).unwrap();
+    let tile = tiles::get_tile_async(reader.clone(), &lazy.levels[0], 777)
+        .await
+        .unwrap();
+    assert_eq!(tile.buffer, expected.buffer);
+    let tiles = tiles::get_tiles_async(reader, &lazy.levels[0], (100..200).collect()).await;
+    assert_eq!(tiles.len(), 100);
+}
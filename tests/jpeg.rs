@@ -0,0 +1,100 @@
+#![cfg(feature = "image")]
+
+use cloudtiff::cog::{Compression, Level, Predictor};
+use cloudtiff::raster::{PhotometricInterpretation, PlanarConfiguration, SampleFormat};
+use cloudtiff::tiff::Endian;
+
+// Two 16x16 RGB JPEG tiles sharing the given JPEGTables
+fn level(jpeg_tables: Option<Vec<u8>>) -> Level {
+    Level {
+        overview: None,
+        subfile_type: 0,
+        dimensions: (32, 16),
+        tile_width: 16,
+        tile_height: 16,
+        compression: Compression::Jpeg,
+        predictor: Predictor::No,
+        interpretation: PhotometricInterpretation::YCbCr,
+        planar_configuration: PlanarConfiguration::Chunky,
+        bits_per_sample: vec![8, 8, 8],
+        sample_format: vec![SampleFormat::Unsigned; 3],
+        extra_samples: vec![],
+        endian: Endian::Little,
+        offsets: vec![0, 0],
+        byte_counts: vec![0, 0],
+        deferred: None,
+        jpeg_tables,
+        ycbcr_subsampling: (2, 2),
+        lerc_parameters: None,
+        nodata: None,
+        color_map: None,
+        reference_black_white: None,
+        mask: None,
+        limits: Default::default(),
+        strips: false,
+    }
+}
+
+// Full JPEG of a 16x16 gradient
+fn jpeg() -> Vec<u8> {
+    let image = image::RgbImage::from_fn(16, 16, |x, y| {
+        image::Rgb([(x * 16) as u8, (y * 16) as u8, 128])
+    });
+    let mut bytes = vec![];
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 95)
+        .encode_image(&image)
+        .unwrap();
+    bytes
+}
+
+// Split a JPEG into the tables (DQT and DHT segments) and the abbreviated stream, as in TIFF
+fn split(jpeg: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut tables = vec![0xFF, 0xD8];
+    let mut stream = vec![0xFF, 0xD8];
+    let mut i = 2;
+    loop {
+        let marker = jpeg[i + 1];
+        if marker == 0xDA {
+            stream.extend_from_slice(&jpeg[i..]);
+            break;
+        }
+        let length = u16::from_be_bytes([jpeg[i + 2], jpeg[i + 3]]) as usize;
+        let segment = &jpeg[i..i + 2 + length];
+        match marker {
+            0xDB | 0xC4 => tables.extend_from_slice(segment),
+            _ => stream.extend_from_slice(segment),
+        }
+        i += 2 + length;
+    }
+    tables.extend_from_slice(&[0xFF, 0xD9]);
+    (tables, stream)
+}
+
+#[test]
+fn tables_merged() {
+    let (tables, stream) = split(&jpeg());
+    let raster = level(Some(tables))
+        .extract_tile_from_bytes(&stream)
+        .unwrap();
+    assert_eq!(raster.interpretation, PhotometricInterpretation::RGB);
+    assert_eq!(raster.buffer.len(), 16 * 16 * 3);
+    let pixel = raster.get_pixel(5, 3).unwrap();
+    for (value, expected) in pixel.iter().zip([80, 48, 128]) {
+        assert!((*value as i32 - expected).abs() < 8, "{pixel:?}");
+    }
+}
+
+#[test]
+fn full_stream_with_tables() {
+    // Tiles may carry their own tables, which redefine the shared ones
+    let jpeg = jpeg();
+    let (tables, _) = split(&jpeg);
+    let raster = level(Some(tables)).extract_tile_from_bytes(&jpeg).unwrap();
+    assert_eq!(raster.buffer.len(), 16 * 16 * 3);
+}
+
+#[test]
+fn missing_tables_reported() {
+    let (_, stream) = split(&jpeg());
+    assert!(level(None).extract_tile_from_bytes(&stream).is_err());
+}
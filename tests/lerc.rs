@@ -0,0 +1,211 @@
+use cloudtiff::cog::{
+    CloudTiffError, Compression, DecompressError, LercParameters, Level, Predictor,
+};
+use cloudtiff::raster::{PhotometricInterpretation, PlanarConfiguration, SampleFormat};
+use cloudtiff::tiff::{Endian, TiffError, TiffLimits};
+
+const WIDTH: usize = 8;
+const HEIGHT: usize = 4;
+
+// Single band 8x4 level holding one LERC tile
+fn level(sample_format: SampleFormat, bits_per_sample: u16) -> Level {
+    Level {
+        overview: None,
+        subfile_type: 0,
+        dimensions: (WIDTH as u32, HEIGHT as u32),
+        tile_width: WIDTH as u32,
+        tile_height: HEIGHT as u32,
+        compression: Compression::ESRILerc,
+        predictor: Predictor::No,
+        interpretation: PhotometricInterpretation::BlackIsZero,
+        planar_configuration: PlanarConfiguration::Chunky,
+        bits_per_sample: vec![bits_per_sample],
+        sample_format: vec![sample_format],
+        extra_samples: vec![],
+        endian: Endian::Little,
+        offsets: vec![0],
+        byte_counts: vec![0],
+        deferred: None,
+        jpeg_tables: None,
+        ycbcr_subsampling: (2, 2),
+        lerc_parameters: Some(LercParameters::default()),
+        nodata: None,
+        color_map: None,
+        reference_black_white: None,
+        mask: None,
+        limits: Default::default(),
+        strips: false,
+    }
+}
+
+// Lerc2 blob around the given value data, with blob size and checksum filled in
+fn blob(version: i32, data_type: i32, z: (f64, f64, f64), ranges: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut bytes = b"Lerc2 ".to_vec();
+    bytes.extend(version.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes()); // checksum
+    bytes.extend((HEIGHT as i32).to_le_bytes());
+    bytes.extend((WIDTH as i32).to_le_bytes());
+    if version >= 4 {
+        bytes.extend(1i32.to_le_bytes()); // depth
+    }
+    bytes.extend(((WIDTH * HEIGHT) as i32).to_le_bytes()); // all valid
+    bytes.extend(8i32.to_le_bytes()); // micro block size
+    let blob_size_at = bytes.len();
+    bytes.extend(0i32.to_le_bytes());
+    bytes.extend(data_type.to_le_bytes());
+    if version >= 6 {
+        bytes.extend(0i32.to_le_bytes()); // no more blobs
+        bytes.extend([0; 4]); // flags
+    }
+    for v in [z.0, z.1, z.2] {
+        bytes.extend(v.to_le_bytes()); // max z error, z min, z max
+    }
+    if version >= 6 {
+        bytes.extend([0; 16]); // no data values
+    }
+    bytes.extend(0i32.to_le_bytes()); // no mask bytes
+    bytes.extend(ranges);
+    bytes.extend(data);
+
+    let blob_size = bytes.len() as i32;
+    bytes[blob_size_at..blob_size_at + 4].copy_from_slice(&blob_size.to_le_bytes());
+    let checksum = fletcher32(&bytes[14..]);
+    bytes[10..14].copy_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+// Lerc2 checksum, written independently of the decoder
+fn fletcher32(bytes: &[u8]) -> u32 {
+    let (mut sum1, mut sum2) = (0xffff_u32, 0xffff_u32);
+    let mut i = 0;
+    let mut words = bytes.len() / 2;
+    while words > 0 {
+        let block = words.min(359);
+        words -= block;
+        for _ in 0..block {
+            sum1 += ((bytes[i] as u32) << 8) + bytes[i + 1] as u32;
+            sum2 += sum1;
+            i += 2;
+        }
+        sum1 = (sum1 & 0xffff) + (sum1 >> 16);
+        sum2 = (sum2 & 0xffff) + (sum2 >> 16);
+    }
+    if bytes.len() % 2 == 1 {
+        sum1 += (bytes[i] as u32) << 8;
+        sum2 += sum1;
+    }
+    sum1 = (sum1 & 0xffff) + (sum1 >> 16);
+    sum2 = (sum2 & 0xffff) + (sum2 >> 16);
+    (sum2 << 16) | sum1
+}
+
+// Byte values stored raw in one sweep
+fn one_sweep_bytes() -> (Vec<u8>, Vec<u8>) {
+    let values: Vec<u8> = (0..WIDTH * HEIGHT).map(|k| k as u8 * 3).collect();
+    let mut data = vec![1]; // one sweep
+    data.extend(&values);
+    (values, data)
+}
+
+#[test]
+fn checksum_verified() {
+    let (values, data) = one_sweep_bytes();
+    let bytes = blob(3, 1, (0.5, 0.0, 93.0), &[], &data);
+    let raster = level(SampleFormat::Unsigned, 8)
+        .extract_tile_from_bytes(&bytes)
+        .unwrap();
+    assert_eq!(raster.buffer, values);
+}
+
+#[test]
+fn checksum_mismatch() {
+    let (_, data) = one_sweep_bytes();
+    let mut bytes = blob(3, 1, (0.5, 0.0, 93.0), &[], &data);
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    let result = level(SampleFormat::Unsigned, 8).extract_tile_from_bytes(&bytes);
+    assert!(matches!(
+        result,
+        Err(CloudTiffError::DecompresionError(
+            DecompressError::LercChecksumMismatch(_)
+        ))
+    ));
+}
+
+#[test]
+fn decoded_values_limited() {
+    // 32 bytes of output, but each value is decoded as an f64 first
+    let (_, data) = one_sweep_bytes();
+    let bytes = blob(3, 1, (0.5, 0.0, 93.0), &[], &data);
+    let mut level = level(SampleFormat::Unsigned, 8);
+    level.limits = TiffLimits::default().with_max_tile_bytes(64);
+    assert!(matches!(
+        level.extract_tile_from_bytes(&bytes),
+        Err(CloudTiffError::BadTiff(TiffError::TileTooLarge(64)))
+    ));
+}
+
+// Blobs written by libLerc 4 with MAX_Z_ERROR=0, which selects the lossless float mode
+const FLOAT_LOSSLESS: &[u8] = include_bytes!("data/lerc/float_lossless.lerc");
+const DOUBLE_LOSSLESS: &[u8] = include_bytes!("data/lerc/double_lossless.lerc");
+// Two bands with -9999 at values 5 and 12, stored by libLerc as a no data value below the range
+const FLOAT_NODATA: &[u8] = include_bytes!("data/lerc/float_nodata.lerc");
+
+#[test]
+fn float_lossless() {
+    // 16x16, byte planes in Huffman and PackBits modes behind the 2D delta predictor
+    let mut level = level(SampleFormat::Float, 32);
+    level.dimensions = (16, 16);
+    level.tile_width = 16;
+    level.tile_height = 16;
+    let raster = level.extract_tile_from_bytes(FLOAT_LOSSLESS).unwrap();
+    let expected: Vec<u8> = (0..16)
+        .flat_map(|y| (0..16).map(move |x| (x * x + 3 * y) as f32 / 7.0))
+        .flat_map(f32::to_le_bytes)
+        .collect();
+    assert_eq!(raster.buffer, expected);
+}
+
+#[test]
+fn double_lossless() {
+    // Byte planes in constant and raw modes without a predictor
+    let raster = level(SampleFormat::Float, 64)
+        .extract_tile_from_bytes(DOUBLE_LOSSLESS)
+        .unwrap();
+    let expected: Vec<u8> = (0..HEIGHT)
+        .flat_map(|y| (0..WIDTH).map(move |x| 1000.0 + (x + 2 * y) as f64 * 0.125))
+        .flat_map(f64::to_le_bytes)
+        .collect();
+    assert_eq!(raster.buffer, expected);
+}
+
+#[test]
+fn no_data_restored() {
+    let mut level = level(SampleFormat::Float, 32);
+    level.bits_per_sample = vec![32; 2];
+    level.sample_format = vec![SampleFormat::Float; 2];
+    let raster = level.extract_tile_from_bytes(FLOAT_NODATA).unwrap();
+    let expected: Vec<u8> = (0..WIDTH * HEIGHT * 2)
+        .map(|k| match k {
+            5 | 12 => -9999.0,
+            _ => k as f32 * 1.5,
+        })
+        .flat_map(f32::to_le_bytes)
+        .collect();
+    assert_eq!(raster.buffer, expected);
+}
+
+#[test]
+fn unknown_mode_reported() {
+    // Version 6 float with MAX_Z_ERROR=0 and a mode byte past the lossless float mode
+    let ranges: Vec<u8> = [0f32, 31.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let data = [0, 4]; // not one sweep, unknown mode
+    let bytes = blob(6, 6, (0.0, 0.0, 31.0), &ranges, &data);
+    let result = level(SampleFormat::Float, 32).extract_tile_from_bytes(&bytes);
+    assert!(matches!(
+        result,
+        Err(CloudTiffError::DecompresionError(
+            DecompressError::LercModeNotSupported(4)
+        ))
+    ));
+}
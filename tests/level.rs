@@ -0,0 +1,67 @@
+use cloudtiff::cog::{CloudTiffError, Level};
+use cloudtiff::raster::{ExtraSamples, PlanarConfiguration};
+use cloudtiff::tiff::{TagId, Tiff, TiffError};
+use std::io::Cursor;
+
+// Little endian classic TIFF with one IFD at offset 8, tags as (code, type, count, value)
+fn tiff(tags: &[(u16, u16, u32, u32)]) -> Tiff {
+    let mut bytes = b"II*\0".to_vec();
+    bytes.extend(8u32.to_le_bytes());
+    bytes.extend((tags.len() as u16).to_le_bytes());
+    for (code, datatype, count, value) in tags {
+        bytes.extend(code.to_le_bytes());
+        bytes.extend(datatype.to_le_bytes());
+        bytes.extend(count.to_le_bytes());
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes.extend(0u32.to_le_bytes());
+    Tiff::open(&mut Cursor::new(bytes)).unwrap()
+}
+
+fn level(tags: &[(u16, u16, u32, u32)]) -> Result<Level, CloudTiffError> {
+    let tiff = tiff(tags);
+    Level::from_ifd(&tiff.ifds[0], tiff.endian)
+}
+
+// 8x4 single band 8 bit strip, followed by the given tags
+fn strip_tags(extra: &[(u16, u16, u32, u32)]) -> Vec<(u16, u16, u32, u32)> {
+    let mut tags = vec![
+        (256, 3, 1, 8),  // ImageWidth
+        (257, 3, 1, 4),  // ImageLength
+        (258, 3, 1, 8),  // BitsPerSample
+        (259, 3, 1, 1),  // Compression
+        (273, 4, 1, 0),  // StripOffsets
+        (278, 3, 1, 4),  // RowsPerStrip
+        (279, 4, 1, 32), // StripByteCounts
+    ];
+    tags.extend(extra);
+    tags
+}
+
+#[test]
+fn extra_samples_beyond_samples() {
+    // Two extra samples on a single sample pixel
+    let result = level(&strip_tags(&[(338, 3, 2, 0x0002_0001)]));
+    assert!(matches!(
+        result,
+        Err(CloudTiffError::BadTiff(TiffError::BadTag(
+            TagId::ExtraSamples
+        )))
+    ));
+
+    let level = level(&strip_tags(&[(338, 3, 1, 2)])).unwrap();
+    assert_eq!(level.extra_samples, vec![ExtraSamples::UnassociatedAlpha]);
+}
+
+#[test]
+fn select_bands_checks_extra_samples() {
+    let mut level = level(&strip_tags(&[])).unwrap();
+    level.planar_configuration = PlanarConfiguration::Planar;
+    level.extra_samples = vec![ExtraSamples::UnassociatedAlpha; 2];
+    assert!(matches!(
+        level.select_bands(&[0]),
+        Err(CloudTiffError::BadTiff(TiffError::BadTag(
+            TagId::ExtraSamples
+        )))
+    ));
+}
@@ -0,0 +1,159 @@
+use cloudtiff::cog::{CloudTiffError, Compression, Level, Predictor};
+use cloudtiff::raster::{PhotometricInterpretation, PlanarConfiguration, SampleFormat};
+use cloudtiff::tiff::{Endian, Tiff, TiffError, TiffLimits};
+use std::io::Cursor;
+
+// Little endian classic TIFF with one IFD at offset 8, tags as (code, type, count, value)
+fn tiff(tags: &[(u16, u16, u32, u32)], next_ifd: u32) -> Vec<u8> {
+    let mut bytes = b"II*\0".to_vec();
+    bytes.extend(8u32.to_le_bytes());
+    bytes.extend((tags.len() as u16).to_le_bytes());
+    for (code, datatype, count, value) in tags {
+        bytes.extend(code.to_le_bytes());
+        bytes.extend(datatype.to_le_bytes());
+        bytes.extend(count.to_le_bytes());
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes.extend(next_ifd.to_le_bytes());
+    bytes.resize(bytes.len() + 1024, 0); // room for out of line tag data
+    bytes
+}
+
+fn open(bytes: Vec<u8>, limits: TiffLimits) -> Result<Tiff, TiffError> {
+    Tiff::open_with_limits(&mut Cursor::new(bytes), limits)
+}
+
+// Single band 8 bit 8x4 level holding one tile
+fn level(compression: Compression, limits: TiffLimits) -> Level {
+    Level {
+        overview: None,
+        subfile_type: 0,
+        dimensions: (8, 4),
+        tile_width: 8,
+        tile_height: 4,
+        compression,
+        predictor: Predictor::No,
+        interpretation: PhotometricInterpretation::BlackIsZero,
+        planar_configuration: PlanarConfiguration::Chunky,
+        bits_per_sample: vec![8],
+        sample_format: vec![SampleFormat::Unsigned],
+        extra_samples: vec![],
+        endian: Endian::Little,
+        offsets: vec![0],
+        byte_counts: vec![32],
+        deferred: None,
+        jpeg_tables: None,
+        ycbcr_subsampling: (2, 2),
+        lerc_parameters: None,
+        nodata: None,
+        color_map: None,
+        reference_black_white: None,
+        mask: None,
+        limits,
+        strips: false,
+    }
+}
+
+#[test]
+fn ifd_loop() {
+    let bytes = tiff(&[(256, 3, 1, 8)], 8);
+    assert!(matches!(
+        open(bytes, TiffLimits::default()),
+        Err(TiffError::IfdLoop(8))
+    ));
+}
+
+#[test]
+fn too_many_tags() {
+    let bytes = tiff(&[(256, 3, 1, 8), (257, 3, 1, 4), (258, 3, 1, 8)], 0);
+    let limits = TiffLimits::default().with_max_tags_per_ifd(2);
+    assert!(matches!(
+        open(bytes, limits),
+        Err(TiffError::TooManyTags((3, 2)))
+    ));
+}
+
+#[test]
+fn tag_too_large() {
+    let bytes = tiff(&[(270, 2, 100, 64)], 0);
+    let limits = TiffLimits::default().with_max_tag_bytes(50);
+    assert!(matches!(
+        open(bytes, limits),
+        Err(TiffError::TagTooLarge((270, 100)))
+    ));
+}
+
+#[test]
+fn header_too_large() {
+    // Each tag is within the tag limit, together they are not
+    let bytes = tiff(&[(270, 2, 100, 64), (305, 2, 100, 164)], 0);
+    let limits = TiffLimits::default()
+        .with_max_tag_bytes(100)
+        .with_max_header_bytes(150);
+    assert!(matches!(
+        open(bytes, limits),
+        Err(TiffError::HeaderTooLarge(150))
+    ));
+
+    let bytes = tiff(&[(270, 2, 100, 64), (305, 2, 100, 164)], 0);
+    let limits = TiffLimits::default().with_max_header_bytes(200);
+    assert!(open(bytes, limits).is_ok());
+}
+
+#[test]
+fn tile_too_large() {
+    let limits = TiffLimits::default().with_max_tile_bytes(16);
+    let result = level(Compression::Uncompressed, limits).extract_tile_from_bytes(&[0; 32]);
+    assert!(matches!(
+        result,
+        Err(CloudTiffError::BadTiff(TiffError::TileTooLarge(16)))
+    ));
+}
+
+#[test]
+fn packbits_output_limited() {
+    // Each run of 2 bytes expands to 128, far past the 32 byte tile
+    let bytes: Vec<u8> = [0x81, 0].repeat(1000);
+    let limits = TiffLimits::default().with_max_tile_bytes(64);
+    let result = level(Compression::PackBits, limits).extract_tile_from_bytes(&bytes);
+    assert!(matches!(
+        result,
+        Err(CloudTiffError::BadTiff(TiffError::TileTooLarge(64)))
+    ));
+}
+
+#[test]
+fn compressed_tile_too_large() {
+    let limits = TiffLimits::default().with_max_compressed_tile_bytes(16);
+    let result = level(Compression::Uncompressed, limits).tile_byte_range(0);
+    assert!(matches!(
+        result,
+        Err(CloudTiffError::BadTiff(TiffError::CompressedTileTooLarge(
+            32
+        )))
+    ));
+}
+
+#[test]
+fn tile_range_overflow() {
+    let mut level = level(Compression::Uncompressed, TiffLimits::default());
+    level.offsets = vec![u64::MAX - 8];
+    assert!(matches!(
+        level.tile_byte_range(0),
+        Err(CloudTiffError::BadTiff(TiffError::BadTag(_)))
+    ));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn sequential_read_limited() {
+    // Tag data far past the IFD, a sequential source would be buffered up to it
+    let bytes = tiff(&[(256, 3, 1, 8), (270, 2, 100, 1_000_000)], 0);
+    let limits = TiffLimits::default().with_max_header_bytes(4096);
+    let result =
+        cloudtiff::cog::CloudTiff::open_async_with_limits(&mut Cursor::new(bytes), limits).await;
+    assert!(matches!(
+        result,
+        Err(CloudTiffError::BadTiff(TiffError::HeaderTooLarge(4096)))
+    ));
+}
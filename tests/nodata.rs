@@ -0,0 +1,113 @@
+use cloudtiff::cog::{Compression, Level, NoData, Predictor};
+use cloudtiff::raster::{PhotometricInterpretation, PlanarConfiguration, Raster, SampleFormat};
+use cloudtiff::tiff::Endian;
+
+// 2x2 gray raster with the second pixel masked out
+fn masked_raster() -> Raster {
+    Raster::new(
+        (2, 2),
+        vec![10, 20, 30, 40],
+        vec![8],
+        PhotometricInterpretation::BlackIsZero,
+        vec![SampleFormat::Unsigned],
+        vec![],
+    )
+    .unwrap()
+    .with_mask(vec![1, 0, 1, 1])
+    .unwrap()
+}
+
+#[test]
+fn unsigned_rejects_negative_and_fractional() {
+    let parse = |text| NoData::parse(text, SampleFormat::Unsigned);
+    assert_eq!(parse("255"), Some(NoData::Unsigned(255)));
+    assert_eq!(parse("255.0"), Some(NoData::Unsigned(255)));
+    assert_eq!(parse("-9999"), None);
+    assert_eq!(parse("-1"), None);
+    assert_eq!(parse("2.5"), None);
+    assert_eq!(parse("nan"), None);
+    assert_eq!(parse("inf"), None);
+}
+
+#[test]
+fn signed_accepts_integral_floats() {
+    let parse = |text| NoData::parse(text, SampleFormat::Signed);
+    assert_eq!(parse("-9999"), Some(NoData::Signed(-9999)));
+    assert_eq!(parse("-9999.0"), Some(NoData::Signed(-9999)));
+    assert_eq!(parse("-9999.5"), None);
+    assert_eq!(parse("1e30"), None);
+}
+
+#[test]
+fn masked_pixels_transparent() {
+    let raster = masked_raster();
+    assert_eq!(raster.get_pixel_rgba8(0, 0), Some([10, 10, 10, 255]));
+    assert_eq!(raster.get_pixel_rgba8(1, 0), Some([20, 20, 20, 0]));
+    let rgba = raster.to_rgba8().unwrap();
+    let alpha: Vec<u8> = rgba.chunks(4).map(|pixel| pixel[3]).collect();
+    assert_eq!(alpha, vec![255, 0, 255, 255]);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn masked_pixels_transparent_rgba32f() {
+    let image = masked_raster().into_rgba32f().unwrap();
+    let alpha: Vec<f32> = image.pixels().map(|pixel| pixel.0[3]).collect();
+    assert_eq!(alpha, vec![1.0, 0.0, 1.0, 1.0]);
+}
+
+#[test]
+fn mask_index_does_not_wrap() {
+    // Row 65536 of a 65536 wide raster starts at 2^32, which wraps to 0 in u32
+    let mut raster = masked_raster();
+    raster.dimensions = (65536, 65537);
+    assert!(raster.is_valid(0, 0));
+    assert!(!raster.is_valid(0, 65536));
+}
+
+// 3x2 single band level of packed 4 bit samples, rows padded to whole bytes
+fn packed_level(format: SampleFormat, nodata: NoData) -> Level {
+    Level {
+        overview: None,
+        subfile_type: 0,
+        dimensions: (3, 2),
+        tile_width: 3,
+        tile_height: 2,
+        compression: Compression::Uncompressed,
+        predictor: Predictor::No,
+        interpretation: PhotometricInterpretation::BlackIsZero,
+        planar_configuration: PlanarConfiguration::Chunky,
+        bits_per_sample: vec![4],
+        sample_format: vec![format],
+        extra_samples: vec![],
+        endian: Endian::Little,
+        offsets: vec![0],
+        byte_counts: vec![4],
+        deferred: None,
+        jpeg_tables: None,
+        ycbcr_subsampling: (2, 2),
+        lerc_parameters: None,
+        nodata: Some(nodata),
+        color_map: None,
+        reference_black_white: None,
+        mask: None,
+        limits: Default::default(),
+        strips: false,
+    }
+}
+
+#[test]
+fn packed_samples_masked() {
+    // Rows [15, 1, 15] and [2, 15, 3]
+    let bytes = [0xF1, 0xF0, 0x2F, 0x30];
+    let raster = packed_level(SampleFormat::Unsigned, NoData::Unsigned(15))
+        .extract_tile_from_bytes(&bytes)
+        .unwrap();
+    assert_eq!(raster.mask, Some(vec![0, 255, 0, 255, 0, 255]));
+
+    // 15 is -1 as a signed 4 bit sample
+    let raster = packed_level(SampleFormat::Signed, NoData::Signed(-1))
+        .extract_tile_from_bytes(&bytes)
+        .unwrap();
+    assert_eq!(raster.mask, Some(vec![0, 255, 0, 255, 0, 255]));
+}
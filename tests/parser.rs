@@ -0,0 +1,122 @@
+use cloudtiff::cog::GdalMetadata;
+use cloudtiff::raster::{PhotometricInterpretation, Raster};
+use cloudtiff::tiff::{ParseStep, Tiff, TiffParser};
+use cloudtiff::{CloudTiff, Encoder, Region};
+use std::io::Cursor;
+use std::sync::Mutex;
+
+// 300x300 COG with 4 levels and 100 kB of metadata, a header far larger than one read
+fn cog(big_tiff: bool) -> Vec<u8> {
+    let raster = Raster::from_samples(
+        (300, 300),
+        (0..300 * 300).map(|i| (i % 251) as u8).collect(),
+        1,
+        PhotometricInterpretation::BlackIsZero,
+    )
+    .unwrap();
+    let mut metadata = GdalMetadata::default();
+    metadata.set("BIG", &"x".repeat(100_000));
+    let mut bytes = Cursor::new(vec![]);
+    Encoder::from_raster(raster)
+        .unwrap()
+        .with_tile_size(64)
+        .with_big_tiff(big_tiff)
+        .with_metadata(metadata)
+        .with_projection(
+            32609,
+            Region::new(500000.0, 6000000.0 - 3000.0, 503000.0, 6000000.0),
+        )
+        .encode(&mut bytes)
+        .unwrap();
+    bytes.into_inner()
+}
+
+#[test]
+fn exact_ranges() {
+    for big_tiff in [false, true] {
+        let bytes = cog(big_tiff);
+        let mut parser = TiffParser::new();
+        let mut rounds = 0;
+        let tiff = loop {
+            match parser.parse().unwrap() {
+                ParseStep::NeedBytes(ranges) => {
+                    rounds += 1;
+                    for range in ranges {
+                        parser.provide(
+                            range.start,
+                            &bytes[range.start as usize..range.end as usize],
+                        );
+                    }
+                }
+                ParseStep::Done(tiff) => break tiff,
+            }
+        };
+        let reference = Tiff::open(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(format!("{tiff}"), format!("{reference}"));
+        assert_eq!(tiff.ifds.len(), 4);
+        // The file header, then at most the entry count, entries and tag data of each IFD
+        assert!(rounds <= 1 + 3 * 4, "{rounds} rounds");
+    }
+}
+
+#[test]
+fn whole_file_in_one_round() {
+    let bytes = cog(false);
+    let mut parser = TiffParser::new();
+    parser.provide(0, &bytes);
+    assert!(matches!(parser.parse().unwrap(), ParseStep::Done(_)));
+}
+
+#[test]
+fn large_header_from_range_reader() {
+    for big_tiff in [false, true] {
+        let reader = Mutex::new(Cursor::new(cog(big_tiff)));
+        let cog = CloudTiff::open_from_range_reader(&reader).unwrap();
+        assert_eq!(cog.levels.len(), 4);
+        assert_eq!(cog.metadata.unwrap().get("BIG").unwrap().len(), 100_000);
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn large_header_async() {
+    for big_tiff in [false, true] {
+        let bytes = cog(big_tiff);
+        let reader = tokio::sync::Mutex::new(Cursor::new(bytes.clone()));
+        let cog = CloudTiff::open_from_async_range_reader(&reader)
+            .await
+            .unwrap();
+        assert_eq!(cog.levels.len(), 4);
+        assert_eq!(cog.metadata.unwrap().get("BIG").unwrap().len(), 100_000);
+
+        // Sequential source
+        let cog = CloudTiff::open_async(&mut Cursor::new(bytes))
+            .await
+            .unwrap();
+        assert_eq!(cog.levels.len(), 4);
+    }
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn read_ahead_past_end() {
+    // Small file, the speculative read goes past its end
+    let raster = Raster::from_samples(
+        (16, 16),
+        vec![1; 256],
+        1,
+        PhotometricInterpretation::BlackIsZero,
+    )
+    .unwrap();
+    let mut bytes = Cursor::new(vec![]);
+    Encoder::from_raster(raster)
+        .unwrap()
+        .with_tile_size(16)
+        .encode(&mut bytes)
+        .unwrap();
+    let reader = tokio::sync::Mutex::new(Cursor::new(bytes.into_inner()));
+    let cog = CloudTiff::open_from_async_range_reader(&reader)
+        .await
+        .unwrap();
+    assert_eq!(cog.levels.len(), 1);
+}
@@ -0,0 +1,105 @@
+use cloudtiff::cog::{CloudTiffError, Compression, DecompressError, Level, Predictor};
+use cloudtiff::raster::{PhotometricInterpretation, PlanarConfiguration, SampleFormat};
+use cloudtiff::tiff::Endian;
+use cloudtiff::{EncodeError, Encoder, Raster};
+use std::io::Cursor;
+
+// 16x16 single band raster of zeros
+fn raster(bits: u16, format: SampleFormat) -> Raster {
+    Raster::new(
+        (16, 16),
+        vec![0; 16 * 16 * bits as usize / 8],
+        vec![bits],
+        PhotometricInterpretation::BlackIsZero,
+        vec![format],
+        vec![],
+    )
+    .unwrap()
+}
+
+// Encoded bytes, or the error with nothing written
+fn encode(raster: Raster, predictor: Predictor) -> Result<Vec<u8>, EncodeError> {
+    let mut cursor = Cursor::new(vec![]);
+    let result = Encoder::from_raster(raster)
+        .unwrap()
+        .with_tile_size(16)
+        .with_predictor(predictor)
+        .encode(&mut cursor);
+    let bytes = cursor.into_inner();
+    match result {
+        Ok(()) => Ok(bytes),
+        Err(e) => {
+            assert!(bytes.is_empty());
+            Err(e)
+        }
+    }
+}
+
+#[test]
+fn encoder_checks_bit_depth() {
+    let result = encode(raster(8, SampleFormat::Unsigned), Predictor::FloatingPoint);
+    assert!(matches!(
+        result,
+        Err(EncodeError::CompressionError(
+            DecompressError::PredictorBitDepthNotSupported((Predictor::FloatingPoint, 8))
+        ))
+    ));
+}
+
+#[test]
+fn encoder_checks_sample_format() {
+    let result = encode(raster(16, SampleFormat::Signed), Predictor::FloatingPoint);
+    assert!(matches!(
+        result,
+        Err(EncodeError::CompressionError(
+            DecompressError::PredictorSampleFormatNotSupported((
+                Predictor::FloatingPoint,
+                SampleFormat::Signed
+            ))
+        ))
+    ));
+}
+
+#[test]
+fn encoder_accepts_matching_predictors() {
+    assert!(encode(raster(16, SampleFormat::Signed), Predictor::Horizontal).is_ok());
+    assert!(encode(raster(32, SampleFormat::Float), Predictor::FloatingPoint).is_ok());
+}
+
+#[test]
+fn decoder_checks_mixed_bit_depths() {
+    // 8x4 chunky tile of a 16 bit sample followed by an 8 bit sample
+    let level = Level {
+        overview: None,
+        subfile_type: 0,
+        dimensions: (8, 4),
+        tile_width: 8,
+        tile_height: 4,
+        compression: Compression::Uncompressed,
+        predictor: Predictor::Horizontal,
+        interpretation: PhotometricInterpretation::BlackIsZero,
+        planar_configuration: PlanarConfiguration::Chunky,
+        bits_per_sample: vec![16, 8],
+        sample_format: vec![SampleFormat::Unsigned; 2],
+        extra_samples: vec![],
+        endian: Endian::Little,
+        offsets: vec![0],
+        byte_counts: vec![96],
+        deferred: None,
+        jpeg_tables: None,
+        ycbcr_subsampling: (2, 2),
+        lerc_parameters: None,
+        nodata: None,
+        color_map: None,
+        reference_black_white: None,
+        mask: None,
+        limits: Default::default(),
+        strips: false,
+    };
+    assert!(matches!(
+        level.extract_tile_from_bytes(&[0; 96]),
+        Err(CloudTiffError::DecompresionError(
+            DecompressError::PredictorBitDepthNotSupported((Predictor::Horizontal, 8))
+        ))
+    ));
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "image")]
+
+use cloudtiff::raster::{PhotometricInterpretation, Raster, SampleFormat};
+
+fn raster(buffer: Vec<u8>, bits: u16, samples: usize, format: SampleFormat) -> Raster {
+    let interpretation = match samples {
+        1 => PhotometricInterpretation::BlackIsZero,
+        _ => PhotometricInterpretation::RGB,
+    };
+    Raster::new(
+        (2, 2),
+        buffer,
+        vec![bits; samples],
+        interpretation,
+        vec![format; samples],
+        vec![],
+    )
+    .unwrap()
+}
+
+// Float RGBA from into_image, quantized as to_rgba8 does
+fn image_rgba8(raster: Raster) -> Vec<u8> {
+    let image = raster.into_image().unwrap().into_rgba32f();
+    image
+        .into_raw()
+        .into_iter()
+        .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect()
+}
+
+#[test]
+fn signed_stretched() {
+    let values = [-100i16, 0, 100, 300];
+    let buffer = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+    let raster = raster(buffer, 16, 1, SampleFormat::Signed);
+    let rgba = raster.to_rgba8().unwrap();
+    let gray: Vec<u8> = rgba.chunks(4).map(|pixel| pixel[0]).collect();
+    assert_eq!(gray, vec![0, 64, 128, 255]);
+    assert_eq!(image_rgba8(raster), rgba);
+}
+
+#[test]
+fn float_rgb_stretched() {
+    let values: Vec<f32> = (0..12).map(|v| v as f32 * 10.0 - 20.0).collect();
+    let buffer = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+    let raster = raster(buffer, 32, 3, SampleFormat::Float);
+    let rgba = raster.to_rgba8().unwrap();
+    assert_eq!(&rgba[..4], &[0, 0, 0, 255]);
+    assert_eq!(&rgba[12..], &[255, 255, 255, 255]);
+    assert_eq!(image_rgba8(raster), rgba);
+}
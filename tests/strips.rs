@@ -0,0 +1,152 @@
+use cloudtiff::cog::Compression;
+use cloudtiff::raster::{PhotometricInterpretation, Raster, SampleFormat};
+use cloudtiff::tiff::{Endian, TagData, TagId, Tiff};
+use cloudtiff::{tiles, CloudTiff, Encoder, Region};
+use std::io::Cursor;
+use std::sync::Mutex;
+
+const WIDTH: u32 = 100;
+const HEIGHT: u32 = 70;
+
+// RGB pixels holding their own (x, y)
+fn pixels() -> Vec<u8> {
+    (0..WIDTH * HEIGHT)
+        .flat_map(|i| [(i % WIDTH) as u8, (i / WIDTH) as u8, 7])
+        .collect()
+}
+
+// Georeferenced RGB TIFF stored in strips of rows_per_strip rows
+fn stripped(rows_per_strip: u32, compression: Compression) -> Vec<u8> {
+    // Tags of a tiled encode, with the tile tags replaced
+    let raster = Raster::new(
+        (WIDTH, HEIGHT),
+        pixels(),
+        vec![8, 8, 8],
+        PhotometricInterpretation::RGB,
+        vec![SampleFormat::Unsigned; 3],
+        vec![],
+    )
+    .unwrap();
+    let mut tiled = Cursor::new(vec![]);
+    Encoder::from_raster(raster)
+        .unwrap()
+        .with_tile_size(256)
+        .with_projection(4326, Region::new(10.0, 40.0, 11.0, 41.0))
+        .encode(&mut tiled)
+        .unwrap();
+    let mut tiff = Tiff::open(&mut Cursor::new(tiled.into_inner())).unwrap();
+    tiff.ifds.truncate(1);
+    let tile_tags = [
+        TagId::TileWidth,
+        TagId::TileLength,
+        TagId::TileOffsets,
+        TagId::TileByteCounts,
+    ];
+    tiff.ifds[0]
+        .0
+        .retain(|tag| !tag.id().is_some_and(|id| tile_tags.contains(&id)));
+
+    let strips: Vec<Vec<u8>> = pixels()
+        .chunks((WIDTH * 3 * rows_per_strip) as usize)
+        .map(|strip| compression.encode(strip).unwrap())
+        .collect();
+    let endian = Endian::Little;
+    let ifd = &mut tiff.ifds[0];
+    ifd.set_tag(
+        TagId::Compression,
+        TagData::Short(vec![compression.into()]),
+        endian,
+    );
+    ifd.set_tag(
+        TagId::RowsPerStrip,
+        TagData::Long(vec![rows_per_strip]),
+        endian,
+    );
+    ifd.set_tag(
+        TagId::StripByteCounts,
+        TagData::Long(strips.iter().map(|strip| strip.len() as u32).collect()),
+        endian,
+    );
+    ifd.0.sort_by_key(|tag| tag.code);
+
+    // Strips follow the header, which is the same size whatever the offsets
+    let header_size = |tiff: &Tiff| {
+        let mut header = Cursor::new(vec![]);
+        tiff.encode(&mut header).unwrap();
+        header.into_inner().len() as u32
+    };
+    let mut offsets = vec![0; strips.len()];
+    tiff.ifds[0].set_tag(TagId::StripOffsets, TagData::Long(offsets.clone()), endian);
+    tiff.ifds[0].0.sort_by_key(|tag| tag.code);
+    let mut offset = header_size(&tiff);
+    for (strip_offset, strip) in offsets.iter_mut().zip(&strips) {
+        *strip_offset = offset;
+        offset += strip.len() as u32;
+    }
+    tiff.ifds[0].set_tag(TagId::StripOffsets, TagData::Long(offsets), endian);
+
+    let mut bytes = Cursor::new(vec![]);
+    tiff.encode(&mut bytes).unwrap();
+    let mut bytes = bytes.into_inner();
+    for strip in strips {
+        bytes.extend(strip);
+    }
+    bytes
+}
+
+#[test]
+fn strips_as_tiles() {
+    for (rows_per_strip, compression) in [
+        (16, Compression::Uncompressed),
+        (7, Compression::DeflateAdobe),
+        (70, Compression::Lzw),
+        (1000, Compression::Uncompressed), // a single strip, cut to the image height
+    ] {
+        let bytes = stripped(rows_per_strip, compression);
+        let cog = CloudTiff::open(&mut Cursor::new(&bytes)).unwrap();
+        let level = &cog.levels[0];
+        let rows = rows_per_strip.min(HEIGHT);
+        assert!(level.strips);
+        assert_eq!((level.tile_width, level.tile_height), (WIDTH, rows));
+        assert_eq!(level.tile_count(), HEIGHT.div_ceil(rows) as usize);
+
+        // The last strip is padded to the full strip height
+        let pixels = pixels();
+        let strip_bytes = (WIDTH * 3 * rows) as usize;
+        let source = Mutex::new(Cursor::new(bytes));
+        let indices = (0..level.tile_count()).collect();
+        for (index, tile) in tiles::get_tiles(&source, level, indices) {
+            assert_eq!(tile.dimensions, (WIDTH, rows));
+            let start = index * strip_bytes;
+            let n = (pixels.len() - start).min(strip_bytes);
+            assert_eq!(tile.buffer[..n], pixels[start..start + n]);
+            assert!(tile.buffer[n..].iter().all(|v| *v == 0));
+        }
+    }
+}
+
+#[test]
+fn strips_from_range_reader() {
+    let bytes = stripped(16, Compression::DeflateAdobe);
+    let reader = Mutex::new(Cursor::new(bytes));
+    let cog = CloudTiff::open_from_range_reader(&reader).unwrap();
+    let level = &cog.levels[0];
+    let tile = tiles::get_tile(&reader, level, level.tile_count() - 1).unwrap();
+    // Rows 64 to 69 of the image
+    assert_eq!(tile.get_pixel(3, 5).unwrap(), vec![3, 69, 7]);
+}
+
+#[test]
+fn strips_rendered() {
+    let bytes = stripped(7, Compression::Lzw);
+    let cog = CloudTiff::open(&mut Cursor::new(&bytes)).unwrap();
+    let source = Mutex::new(Cursor::new(bytes));
+    let raster = cog
+        .renderer()
+        .with_exact_resolution((WIDTH / 2, HEIGHT / 2))
+        .with_reader(&source)
+        .render()
+        .unwrap();
+    assert_eq!(raster.dimensions, (WIDTH / 2, HEIGHT / 2));
+    assert!(raster.mask.unwrap().iter().all(|valid| *valid != 0));
+}